@@ -0,0 +1,107 @@
+//! Banners shown when the shell opens: the welcome header and the FIGlet
+//! greeting, in a font embedded in the binary or read from a `.flf` file.
+
+use crate::{table, theme::Paint};
+use colored::Colorize;
+use figlet_rs::FIGfont;
+use log::warn;
+
+/// Fonts built into the binary, besides figlet's own `standard`.
+const FONTS: [(&str, &str); 1] = [("isometric3", include_str!("../resources/isometric3.flf"))];
+pub const DEFAULT_FONT: &str = "isometric3";
+
+/// Prints the name, version and home of the CLI.
+pub fn welcome() {
+    let width = table::width();
+    println!(
+        "{:~^width$}\n{:^width$}\n{:^width$}\n{:^width$}",
+        " Gamify CLI ".accent().bold(),
+        env!("CARGO_PKG_VERSION").accent(),
+        "https://github.com/darklamp/gamify-rust".link(),
+        "Press CTRL+D to exit or type help to get help.".muted(),
+        width = width,
+    );
+}
+
+/// Greets `username` in big letters, `font` being the name of a built-in
+/// font or the path of a `.flf` file. Falls back to the default font if it
+/// can't be loaded, and to plain text if nothing fits the terminal.
+pub fn greet(username: &str, font: Option<&str>) {
+    let font = match font.map(load) {
+        Some(Ok(f)) => Some(f),
+        Some(Err(e)) => {
+            warn!("Can't load font {}: {}", font.unwrap_or_default(), e);
+            load(DEFAULT_FONT).ok()
+        }
+        None => load(DEFAULT_FONT).ok(),
+    };
+    let greeting = match font {
+        Some(f) => greeting(&f, username, table::width()),
+        None => format!("Hi {}", username),
+    };
+    println!("{}", greeting.info());
+}
+
+fn load(name: &str) -> Result<FIGfont, String> {
+    if name == "standard" {
+        return FIGfont::standand();
+    }
+    match FONTS.iter().find(|(n, _)| *n == name) {
+        Some((_, content)) => FIGfont::from_content(content),
+        None => FIGfont::from_file(name),
+    }
+}
+
+/// "Hi username" in `font`, on two lines if one is too wide for `width`.
+fn greeting(font: &FIGfont, username: &str, width: usize) -> String {
+    // characters missing from the font would silently be left out
+    let figure = |text: &str| {
+        if !text.chars().all(|c| font.convert(&c.to_string()).is_some()) {
+            return None;
+        }
+        font.convert(text)
+            .map(|f| f.to_string())
+            .filter(|f| f.lines().all(|l| table::display_width(l) <= width))
+    };
+
+    if let Some(f) = figure(&format!("Hi {}", username)) {
+        return f;
+    }
+    match (figure("Hi"), figure(username)) {
+        (Some(hi), Some(name)) => format!("{}\n{}", hi, name),
+        _ => format!("Hi {}", username),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_the_built_in_fonts() {
+        assert!(load(DEFAULT_FONT).is_ok());
+        assert!(load("standard").is_ok());
+        assert!(load("/nonexistent/font.flf").is_err());
+    }
+
+    #[test]
+    fn fits_the_greeting_in_the_terminal() {
+        let font = load(DEFAULT_FONT).unwrap();
+        let wide = greeting(&font, "mr", 200);
+        assert!(wide.lines().count() > 1);
+        let widest = |s: &str| s.lines().map(table::display_width).max().unwrap();
+        assert!(widest(&wide) <= 200);
+
+        let narrow = greeting(&font, "mr", widest(&wide) - 1);
+        assert!(widest(&narrow) < widest(&wide));
+        assert!(narrow.lines().count() > wide.lines().count());
+
+        assert_eq!(greeting(&font, "mr", 10), "Hi mr");
+    }
+
+    #[test]
+    fn falls_back_to_text_for_missing_characters() {
+        let font = load(DEFAULT_FONT).unwrap();
+        assert_eq!(greeting(&font, "José", 200), "Hi José");
+    }
+}
@@ -0,0 +1,247 @@
+//! Browsing the users of a questionnaire in `inspect`: reading the answers of
+//! one user after the other, narrowing the list down, and switching between
+//! the users who completed the questionnaire and those who canceled it.
+
+use crate::{
+    compact, inspect, progress, prompt, showAnswers, table, theme::Paint, users_table, User,
+};
+use chrono::NaiveDate;
+use clap::ArgMatches;
+use dialoguer::console::{Key, Term};
+use reqwest::blocking::Client;
+use std::fmt;
+
+/// Which users to show, e.g. `ann sex=F born=1990..1999`: words in the
+/// username, the sex, and a range of birth years or dates.
+#[derive(Debug, Default)]
+pub struct Filter {
+    text: String,
+    names: Vec<String>,
+    sex: Option<String>,
+    born_from: Option<NaiveDate>,
+    born_to: Option<NaiveDate>,
+}
+
+impl Filter {
+    pub fn parse(text: &str) -> Result<Filter, String> {
+        let mut filter = Filter {
+            text: text.trim().to_string(),
+            ..Filter::default()
+        };
+        for word in text.split_whitespace() {
+            if let Some(sex) = word.strip_prefix("sex=") {
+                filter.sex = Some(sex.to_uppercase());
+            } else if let Some(range) = word.strip_prefix("born=") {
+                let (from, to) = range.split_once("..").unwrap_or((range, range));
+                filter.born_from = bound(from, true)?;
+                filter.born_to = bound(to, false)?;
+            } else {
+                filter.names.push(word.to_lowercase());
+            }
+        }
+        Ok(filter)
+    }
+
+    pub fn matches(&self, user: &User) -> bool {
+        let name = user.username.to_lowercase();
+        self.names.iter().all(|n| name.contains(n))
+            && self
+                .sex
+                .as_ref()
+                .is_none_or(|s| user.sex.eq_ignore_ascii_case(s))
+            && self.born_from.is_none_or(|d| user.birth >= d)
+            && self.born_to.is_none_or(|d| user.birth <= d)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+/// A year, the first or last day of it, or a YYYY-MM-DD date. Empty for an
+/// open range.
+fn bound(s: &str, first: bool) -> Result<Option<NaiveDate>, String> {
+    if s.is_empty() {
+        return Ok(None);
+    }
+    let date = match s.parse::<i32>() {
+        Ok(year) if first => NaiveDate::from_ymd_opt(year, 1, 1),
+        Ok(year) => NaiveDate::from_ymd_opt(year, 12, 31),
+        Err(_) => NaiveDate::parse_from_str(s, "%Y-%m-%d").ok(),
+    };
+    date.map(Some)
+        .ok_or_else(|| format!("Invalid birth {}, expected a year or YYYY-MM-DD.", s))
+}
+
+/// Lets the admin go through `users` of questionnaire `id` until they leave.
+pub fn users(
+    client: &Client,
+    id: &str,
+    canceled: bool,
+    users: Vec<User>,
+    mut filter: Filter,
+    args: &ArgMatches,
+) -> bool {
+    // fetched when first shown
+    let mut lists: [Option<Vec<User>>; 2] = [None, None];
+    let mut canceled = canceled;
+    lists[canceled as usize] = Some(users);
+    let mut at = 0;
+
+    loop {
+        let users = match &lists[canceled as usize] {
+            Some(u) => u,
+            None => return false,
+        };
+        let shown: Vec<&User> = users.iter().filter(|u| filter.matches(u)).collect();
+        let mut table = users_table(shown.iter().copied());
+        if !table.configure(args) {
+            return false;
+        }
+        let order = table.order();
+
+        let (kind, other) = if canceled {
+            ("canceled", "completed")
+        } else {
+            ("completed", "canceled")
+        };
+        let mut title = format!(
+            "{} of {} users {} questionnaire {}",
+            shown.len(),
+            users.len(),
+            kind,
+            id
+        );
+        if !filter.is_empty() {
+            title += &format!(", filtered by {}", filter);
+        }
+        println!("{}", title.info());
+
+        let width = table::width().saturating_sub(3);
+        let header = format!("  {}", table.header(width));
+        let mut items = table.rows(width);
+        let actions = [
+            format!("Show the users who {} it", other),
+            if filter.is_empty() {
+                "Filter by name, sex=F or born=1990..1999".to_string()
+            } else {
+                format!("Change the filter ({})", filter)
+            },
+            "Leave".to_string(),
+        ];
+        items.extend(actions.iter().map(|a| a.muted().to_string()));
+
+        let choice = match prompt::select_at(&header, &items, at.min(items.len() - 1)) {
+            Some(c) => c,
+            None => return true,
+        };
+        match choice.checked_sub(shown.len()) {
+            None => at = read(client, id, &shown, &order, choice, compact(args)),
+            Some(0) => {
+                canceled = !canceled;
+                if lists[canceled as usize].is_none() {
+                    match inspect(client, id, canceled) {
+                        Ok(u) => lists[canceled as usize] = Some(u),
+                        Err(()) => {
+                            println!("{}", format!("Can't get the {} users.", other).error());
+                            canceled = !canceled;
+                        }
+                    }
+                }
+                at = 0;
+            }
+            Some(1) => {
+                let text = filter.to_string();
+                let input = prompt::input("Filter (empty for everybody)", Some(&text));
+                match input.as_deref().map(Filter::parse) {
+                    Some(Ok(f)) => filter = f,
+                    Some(Err(e)) => println!("{}", e.error()),
+                    None => {}
+                }
+                at = 0;
+            }
+            Some(_) => return true,
+        }
+        if progress::cancelled() {
+            return true;
+        }
+        println!();
+    }
+}
+
+/// Shows the answers of the user at `at` in the list, then of the next or
+/// previous ones as the admin asks. Returns where the admin stopped.
+fn read(
+    client: &Client,
+    id: &str,
+    users: &[&User],
+    order: &[usize],
+    mut at: usize,
+    compact: bool,
+) -> usize {
+    let term = Term::stdout();
+    loop {
+        let user = users[order[at]];
+        println!(
+            "\n{} #{} ({}/{})",
+            user.username.accent(),
+            user.userId.to_string().info(),
+            at + 1,
+            users.len()
+        );
+        showAnswers(client, id, user.userId, compact);
+        println!(
+            "{}",
+            "←/p previous  →/n next  any other key: back to the users".muted()
+        );
+        let key = term.read_key();
+        if progress::cancelled() {
+            return at;
+        }
+        match key {
+            Ok(Key::ArrowLeft) | Ok(Key::Char('p')) if at > 0 => at -= 1,
+            Ok(Key::ArrowRight) | Ok(Key::Char('n')) if at + 1 < users.len() => at += 1,
+            _ => return at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(name: &str, sex: &str, birth: &str) -> User {
+        User {
+            userId: 1,
+            birth: NaiveDate::parse_from_str(birth, "%Y-%m-%d").unwrap(),
+            sex: sex.to_string(),
+            username: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn filters_by_name_sex_and_birth() {
+        let anna = user("Anna", "F", "1990-05-04");
+        let bob = user("bob", "M", "1985-11-23");
+
+        let filter = Filter::parse("ann").unwrap();
+        assert!(filter.matches(&anna) && !filter.matches(&bob));
+        let filter = Filter::parse("sex=m").unwrap();
+        assert!(!filter.matches(&anna) && filter.matches(&bob));
+        let filter = Filter::parse("born=1986..").unwrap();
+        assert!(filter.matches(&anna) && !filter.matches(&bob));
+        let filter = Filter::parse("born=1985").unwrap();
+        assert!(!filter.matches(&anna) && filter.matches(&bob));
+        let filter = Filter::parse("born=..1990-05-03 sex=F").unwrap();
+        assert!(!filter.matches(&anna) && !filter.matches(&bob));
+        assert!(Filter::parse("").unwrap().matches(&bob));
+
+        assert!(Filter::parse("born=the-eighties").is_err());
+    }
+}
@@ -0,0 +1,320 @@
+//! Replies of the server kept on disk for a while, so that going back and
+//! forth between questionnaires and users doesn't ask for them again.
+//!
+//! Only the lists of questionnaires, the lists of users and the answers are
+//! kept, each for as long as `cache_ttl` in the config says. Creating,
+//! updating or deleting a questionnaire drops the lists of questionnaires.
+//! `--no-cache` or `cache: false` in the config turn the cache off.
+
+use crate::{history, http::Reply, progress::human_bytes, theme::Paint, CONFIG, ENDPOINT, MATCHES};
+use clap::ArgMatches;
+use lazy_static::lazy_static;
+use log::{debug, warn};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::PathBuf,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Seconds each kind of reply is kept, 0 not to keep it.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Ttl {
+    pub list: u64,
+    /// Users who completed or canceled a questionnaire.
+    pub users: u64,
+    pub answers: u64,
+}
+
+impl Default for Ttl {
+    fn default() -> Self {
+        Ttl {
+            list: 60,
+            users: 60,
+            // given once and for all
+            answers: 24 * 60 * 60,
+        }
+    }
+}
+
+lazy_static! {
+    static ref STORE: Option<Store> = if CONFIG.cache && !MATCHES.is_present("no-cache") {
+        dirs::cache_dir().map(|d| Store {
+            dir: d.join("gamify-rust").join(history::profile()),
+            base: CONFIG.base_link.clone(),
+        })
+    } else {
+        None
+    };
+}
+
+/// Whether the kept replies are passed over, see `fresh`.
+static FRESH: AtomicBool = AtomicBool::new(false);
+
+/// A directory of replies, one file each.
+struct Store {
+    dir: PathBuf,
+    /// Base link the replies came from, as the profile may point elsewhere
+    /// later on.
+    base: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    base: String,
+    url: String,
+    /// Seconds since the epoch.
+    stored: u64,
+    body: String,
+}
+
+pub fn command(args: &ArgMatches) -> bool {
+    let store = match &*STORE {
+        Some(s) => s,
+        None => {
+            println!("{}", "The cache is off.".warning());
+            return args.subcommand().is_none();
+        }
+    };
+    match args.subcommand() {
+        Some(("clear", _)) => match store.clear() {
+            Ok(n) => {
+                println!("{}", format!("{} replies removed.", n).success());
+                true
+            }
+            Err(e) => {
+                println!("{}", e.error());
+                false
+            }
+        },
+        _ => {
+            let (count, bytes) = store.size();
+            println!(
+                "{} replies ({}) in {}",
+                count.to_string().info(),
+                human_bytes(bytes),
+                store.dir.display().to_string().link()
+            );
+            let ttl = &CONFIG.cache_ttl;
+            println!(
+                "{}",
+                format!(
+                    "Kept for {} s (questionnaires), {} s (users) and {} s (answers).",
+                    ttl.list, ttl.users, ttl.answers
+                )
+                .muted()
+            );
+            true
+        }
+    }
+}
+
+/// How long replies to `url` are kept, if at all.
+fn ttl(url: &str) -> Option<Duration> {
+    let path = url.split('?').next().unwrap_or(url);
+    let ttl = &CONFIG.cache_ttl;
+    let seconds = match path {
+        ENDPOINT::ADMIN_LIST => ttl.list,
+        ENDPOINT::ADMIN_INSPECT | ENDPOINT::ADMIN_INSPECT_CANCELED => ttl.users,
+        ENDPOINT::ADMIN_ANSWERS_RETRIEVAL => ttl.answers,
+        _ => 0,
+    };
+    Some(Duration::from_secs(seconds)).filter(|d| !d.is_zero())
+}
+
+/// Runs `operation` asking the server for everything again, keeping what it
+/// replies, for what changes by the minute (`watch`).
+pub fn fresh<T>(operation: impl FnOnce() -> T) -> T {
+    FRESH.store(true, Ordering::SeqCst);
+    let result = operation();
+    FRESH.store(false, Ordering::SeqCst);
+    result
+}
+
+/// The kept reply to GET `url`, relative to the base link, if still fresh.
+pub fn get(url: &str) -> Option<Reply> {
+    if FRESH.load(Ordering::SeqCst) {
+        return None;
+    }
+    let store = STORE.as_ref()?;
+    let body = store.get(url, ttl(url)?, SystemTime::now())?;
+    Some(Reply {
+        status: StatusCode::OK,
+        body,
+    })
+}
+
+/// Keeps the reply to GET `url`, when it's of the kind kept.
+pub fn put(url: &str, reply: &Reply) {
+    if let (Some(store), Some(_)) = (&*STORE, ttl(url)) {
+        if reply.status == StatusCode::OK {
+            store.put(url, &reply.body, SystemTime::now());
+        }
+    }
+}
+
+/// Drops what a successful request to `url` made stale: the lists of
+/// questionnaires, after one was created, updated or deleted.
+pub fn invalidate(url: &str) {
+    let path = url.split('?').next().unwrap_or(url);
+    if let Some(store) = &*STORE {
+        if [
+            ENDPOINT::ADMIN_CREATE,
+            ENDPOINT::ADMIN_UPDATE,
+            ENDPOINT::ADMIN_DELETE,
+        ]
+        .contains(&path)
+        {
+            store.remove(ENDPOINT::ADMIN_LIST);
+        }
+    }
+}
+
+/// `url` as a file name, `%` escaping what can't be in one.
+fn file_name(url: &str) -> String {
+    let mut name = String::new();
+    for b in url.bytes() {
+        match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' | b'.' => name.push(b as char),
+            _ => name.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    name + ".json"
+}
+
+fn seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+impl Store {
+    fn get(&self, url: &str, ttl: Duration, now: SystemTime) -> Option<String> {
+        let content = fs::read_to_string(self.dir.join(file_name(url))).ok()?;
+        let entry: Entry = serde_json::from_str(&content).ok()?;
+        let age = seconds(now).saturating_sub(entry.stored);
+        if entry.base != self.base || entry.url != url || age >= ttl.as_secs() {
+            return None;
+        }
+        debug!("GET {} from the cache, {} s old", url, age);
+        Some(entry.body)
+    }
+
+    fn put(&self, url: &str, body: &str, now: SystemTime) {
+        let entry = Entry {
+            base: self.base.clone(),
+            url: url.to_string(),
+            stored: seconds(now),
+            body: body.to_string(),
+        };
+        let saved = fs::create_dir_all(&self.dir).and_then(|_| {
+            fs::write(
+                self.dir.join(file_name(url)),
+                serde_json::to_string(&entry)?,
+            )
+        });
+        if let Err(e) = saved {
+            warn!("Can't cache {}: {}", url, e);
+        }
+    }
+
+    /// Drops the replies to the URLs starting with `prefix`.
+    fn remove(&self, prefix: &str) {
+        let prefix = file_name(prefix);
+        let prefix = prefix.trim_end_matches(".json");
+        for path in self.files() {
+            let matches = path
+                .file_name()
+                .is_some_and(|n| n.to_string_lossy().starts_with(prefix));
+            if matches {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+
+    /// Drops every reply, telling how many there were.
+    fn clear(&self) -> Result<usize, String> {
+        let files = self.files();
+        for path in &files {
+            fs::remove_file(path).map_err(|e| format!("Can't remove {}: {}", path.display(), e))?;
+        }
+        Ok(files.len())
+    }
+
+    /// How many replies are kept, and their size.
+    fn size(&self) -> (usize, u64) {
+        let files = self.files();
+        let bytes = files
+            .iter()
+            .filter_map(|p| fs::metadata(p).ok())
+            .map(|m| m.len())
+            .sum();
+        (files.len(), bytes)
+    }
+
+    fn files(&self) -> Vec<PathBuf> {
+        fs::read_dir(&self.dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok().map(|e| e.path()))
+                    .filter(|p| p.extension().is_some_and(|e| e == "json"))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    #[test]
+    fn keeps_replies_for_a_while() {
+        let dir = env::temp_dir().join(format!("gamify-cache-{}", process::id()));
+        let store = Store {
+            dir: dir.clone(),
+            base: "http://localhost/GamifyUser/".to_string(),
+        };
+        let list = "admin/listQuestionnaires?start=0&size=10&past=true";
+        let users = "admin/listQuestionnaireCompletedUsers?id=1&start=0&size=100";
+        let then = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let minute = Duration::from_secs(60);
+        store.put(list, "[]", then);
+        store.put(users, "[1]", then);
+
+        assert_eq!(
+            store.get(list, minute, then + minute / 2),
+            Some("[]".to_string())
+        );
+        assert_eq!(store.get(list, minute, then + minute), None);
+        assert_eq!(
+            store.get("admin/listQuestionnaires?start=10", minute, then),
+            None
+        );
+        assert_eq!(store.size().0, 2);
+
+        let elsewhere = Store {
+            dir: dir.clone(),
+            base: "http://example.com/GamifyUser/".to_string(),
+        };
+        assert_eq!(elsewhere.get(users, minute, then), None);
+
+        store.remove(ENDPOINT::ADMIN_LIST);
+        assert_eq!(store.get(list, minute, then), None);
+        assert_eq!(store.get(users, minute, then), Some("[1]".to_string()));
+        assert_eq!(store.clear(), Ok(1));
+        assert_eq!(store.size(), (0, 0));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn names_files_after_urls() {
+        assert_eq!(
+            file_name("admin/getAnswers?questionnaireId=1&userId=2"),
+            "admin%2FgetAnswers%3FquestionnaireId%3D1%26userId%3D2.json"
+        );
+        assert_ne!(file_name("a=b"), file_name("a_b"));
+    }
+}
@@ -0,0 +1,302 @@
+//! The `calendar` command: the questionnaires of a month on a grid, to spot
+//! the days left without a questionnaire of the day, or with more than one.
+
+use crate::{
+    admin_command, all_questionnaires, date, inspect, prompt, shell, table,
+    theme::{Paint, Role},
+};
+use chrono::{Datelike, Duration, NaiveDate};
+use clap::ArgMatches;
+use colored::Colorize;
+use reqwest::blocking::Client;
+use std::collections::BTreeMap;
+
+/// A questionnaire on the calendar.
+struct Entry {
+    id: i32,
+    name: String,
+    /// Users who completed it, for the ones already open.
+    answers: Option<usize>,
+}
+
+pub fn command(client: &Client, args: &ArgMatches) -> bool {
+    let today = date::today();
+    let month = match args.value_of("month").map(|m| parse_month(m, today)) {
+        Some(Ok(m)) => m,
+        Some(Err(e)) => {
+            println!("{}", e.error());
+            return false;
+        }
+        None => today.with_day(1).unwrap(),
+    };
+
+    // until the admin leaves, as creating a questionnaire changes the month
+    loop {
+        let days = match days(client, month, today) {
+            Ok(d) => d,
+            Err(()) => {
+                println!("{}", "Can't get the questionnaires.".error());
+                return false;
+            }
+        };
+        let width = table::width();
+        let lines = render(month, &days, today, cell_width(width));
+        let indent = " ".repeat(width.saturating_sub(table::display_width(&lines[1])) / 2);
+        for line in &lines {
+            println!("{}{}", indent, line);
+        }
+        println!("\n{}{}", indent, summary(month, &days, today));
+
+        if !prompt::is_interactive() {
+            return true;
+        }
+        let choices = choices(month, &days, today);
+        let labels: Vec<&str> = choices.iter().map(|(label, _)| label.as_str()).collect();
+        let command = match prompt::select("Open", &labels) {
+            Some(i) if i > 0 => &choices[i].1,
+            _ => return true,
+        };
+        if let Ok(Some(matches)) = shell::parse_admin_words(command) {
+            if let Some((name, args)) = matches.subcommand() {
+                admin_command(client, name, args);
+                println!();
+            }
+        }
+    }
+}
+
+/// `YYYY-MM`, or the number of a month of this year.
+fn parse_month(s: &str, today: NaiveDate) -> Result<NaiveDate, String> {
+    let s = s.trim();
+    let month = match s.parse::<u32>() {
+        Ok(m) => NaiveDate::from_ymd_opt(today.year(), m, 1),
+        Err(_) => NaiveDate::parse_from_str(&format!("{}-01", s), "%Y-%m-%d").ok(),
+    };
+    month.ok_or_else(|| format!("Invalid month {}, expected YYYY-MM or 1-12.", s))
+}
+
+fn days_in(month: NaiveDate) -> impl Iterator<Item = NaiveDate> {
+    month
+        .iter_days()
+        .take_while(move |d| d.month() == month.month())
+}
+
+/// The questionnaires of `month` by day, with their answers up to `today`.
+fn days(
+    client: &Client,
+    month: NaiveDate,
+    today: NaiveDate,
+) -> Result<BTreeMap<NaiveDate, Vec<Entry>>, ()> {
+    let mut days: BTreeMap<NaiveDate, Vec<Entry>> = BTreeMap::new();
+    for q in all_questionnaires(client)? {
+        if (q.datetime.year(), q.datetime.month()) != (month.year(), month.month()) {
+            continue;
+        }
+        let answers = if q.datetime <= today {
+            inspect(client, &q.questionnaireId.to_string(), false)
+                .map(|users| users.len())
+                .ok()
+        } else {
+            None
+        };
+        days.entry(q.datetime).or_default().push(Entry {
+            id: q.questionnaireId,
+            name: q.name,
+            answers,
+        });
+    }
+    Ok(days)
+}
+
+/// Width of a day, so that a week fits `width`.
+fn cell_width(width: usize) -> usize {
+    ((width + 1) / 7).saturating_sub(1).clamp(4, 10)
+}
+
+/// The grid: title, week days, then two lines a week, the day numbers and
+/// what's on those days.
+fn render(
+    month: NaiveDate,
+    days: &BTreeMap<NaiveDate, Vec<Entry>>,
+    today: NaiveDate,
+    width: usize,
+) -> Vec<String> {
+    let total = width * 7 + 6;
+    let title = format!("{:^total$}", date::localized(month, "%B %Y"), total = total);
+    let mut lines = vec![title.trim_end().accent().bold().to_string()];
+
+    let monday = month - Duration::days(month.weekday().num_days_from_monday() as i64);
+    let names: Vec<String> = (0..7)
+        .map(|i| {
+            let name = date::localized(monday + Duration::days(i), "%a");
+            format!("{:^width$}", name, width = width)
+                .muted()
+                .to_string()
+        })
+        .collect();
+    lines.push(names.join(" "));
+
+    // the days of the previous month in the first week stay blank
+    let lead = (month - monday).num_days() as usize;
+    let mut numbers = vec![" ".repeat(width); lead];
+    let mut contents = numbers.clone();
+
+    for day in days_in(month) {
+        let number = if day == today {
+            format!("{:^width$}", format!("[{}]", day.day()), width = width)
+                .accent()
+                .bold()
+        } else {
+            format!("{:^width$}", day.day(), width = width).normal()
+        };
+        numbers.push(number.to_string());
+        contents.push(cell(days.get(&day).map(Vec::as_slice), day, today, width));
+
+        if numbers.len() == 7 || day.succ().month() != month.month() {
+            lines.push(numbers.join(" ").trim_end().to_string());
+            lines.push(contents.join(" ").trim_end().to_string());
+            numbers.clear();
+            contents.clear();
+        }
+    }
+    lines
+}
+
+/// What's on `day`, painted: past questionnaires with their answers, future
+/// ones, several on one day, or none.
+fn cell(entries: Option<&[Entry]>, day: NaiveDate, today: NaiveDate, width: usize) -> String {
+    let (text, role) = match entries {
+        None | Some([]) if day < today => ("·".to_string(), Role::Muted),
+        None | Some([]) => ("·".to_string(), Role::Warning),
+        Some([e]) => {
+            let text = match e.answers {
+                Some(n) => format!("#{} ({})", e.id, n),
+                None => format!("#{}", e.id),
+            };
+            let role = match day {
+                d if d < today => Role::Info,
+                d if d == today => Role::Accent,
+                _ => Role::Success,
+            };
+            (text, role)
+        }
+        Some([e, rest @ ..]) => (format!("#{}+{}", e.id, rest.len()), Role::Error),
+    };
+    let text = table::truncate(&text, width);
+    format!("{:^width$}", text, width = width)
+        .paint(role)
+        .to_string()
+}
+
+fn summary(month: NaiveDate, days: &BTreeMap<NaiveDate, Vec<Entry>>, today: NaiveDate) -> String {
+    let count: usize = days.values().map(Vec::len).sum();
+    let gaps = days_in(month)
+        .filter(|d| *d >= today && !days.contains_key(d))
+        .count();
+    let collisions = days.values().filter(|e| e.len() > 1).count();
+    format!(
+        "{} questionnaires, {} days to come without one, {} days with several.",
+        count.to_string().info(),
+        gaps.to_string()
+            .paint(if gaps > 0 { Role::Warning } else { Role::Info }),
+        collisions.to_string().paint(if collisions > 0 {
+            Role::Error
+        } else {
+            Role::Info
+        }),
+    )
+}
+
+/// What can be opened from the calendar, with the shell command doing it:
+/// inspecting a questionnaire, or creating one on a free day to come.
+fn choices(
+    month: NaiveDate,
+    days: &BTreeMap<NaiveDate, Vec<Entry>>,
+    today: NaiveDate,
+) -> Vec<(String, Vec<String>)> {
+    let mut choices = vec![("Leave the calendar".to_string(), Vec::new())];
+    for day in days_in(month) {
+        let label = date::localized(day, "%a %e");
+        match days.get(&day) {
+            Some(entries) => {
+                for e in entries {
+                    choices.push((
+                        format!("{}  inspect #{} {}", label, e.id, e.name),
+                        vec!["inspect".to_string(), e.id.to_string()],
+                    ));
+                }
+            }
+            None if day >= today => choices.push((
+                format!("{}  create a questionnaire", label),
+                vec!["create".to_string(), "-d".to_string(), day.to_string()],
+            )),
+            None => {}
+        }
+    }
+    choices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn entry(id: i32, answers: Option<usize>) -> Entry {
+        Entry {
+            id,
+            name: format!("Q{}", id),
+            answers,
+        }
+    }
+
+    #[test]
+    fn parses_months() {
+        let today = date("2021-03-15");
+        assert_eq!(parse_month("2021-01", today), Ok(date("2021-01-01")));
+        assert_eq!(parse_month("7", today), Ok(date("2021-07-01")));
+        assert!(parse_month("13", today).is_err());
+        assert!(parse_month("January", today).is_err());
+    }
+
+    #[test]
+    fn renders_the_month_on_a_grid() {
+        let mut days = BTreeMap::new();
+        days.insert(date("2021-02-01"), vec![entry(1, Some(3))]);
+        days.insert(date("2021-02-10"), vec![entry(2, Some(0)), entry(3, None)]);
+        days.insert(date("2021-02-20"), vec![entry(4, None)]);
+        let lines: Vec<String> = render(date("2021-02-01"), &days, date("2021-02-10"), 6)
+            .iter()
+            .map(|l| table::strip_ansi(l))
+            .collect();
+
+        // February 2021 starts on a Monday and takes exactly 4 weeks
+        assert_eq!(lines.len(), 2 + 4 * 2);
+        assert_eq!(lines[0].trim(), "February 2021");
+        assert_eq!(lines[1], " Mon    Tue    Wed    Thu    Fri    Sat    Sun  ");
+        assert_eq!(lines[2], "  1      2      3      4      5      6      7");
+        assert_eq!(lines[3], "#1 (3)   ·      ·      ·      ·      ·      ·");
+        assert_eq!(lines[4].split_whitespace().nth(2), Some("[10]"));
+        assert_eq!(lines[5].split_whitespace().nth(2), Some("#2+1"));
+        assert_eq!(lines[7].split_whitespace().nth(5), Some("#4"));
+    }
+
+    #[test]
+    fn offers_to_inspect_or_fill_the_days() {
+        let mut days = BTreeMap::new();
+        days.insert(date("2021-02-01"), vec![entry(1, Some(3))]);
+        let choices = choices(date("2021-02-01"), &days, date("2021-02-27"));
+        let commands: Vec<String> = choices.iter().map(|(_, c)| c.join(" ")).collect();
+        assert_eq!(
+            commands,
+            [
+                "",
+                "inspect 1",
+                "create -d 2021-02-27",
+                "create -d 2021-02-28"
+            ]
+        );
+    }
+}
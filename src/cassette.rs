@@ -0,0 +1,182 @@
+//! Recording of the requests sent to the server and of their responses into
+//! cassette files, and replay of those instead of contacting the server.
+//!
+//! Requests are matched on method and URL relative to the base link, so a
+//! cassette recorded against one server replays against any other. Each
+//! recorded response is served once, in order, so a `list` before and after
+//! a `delete` gets the two different answers it got when recorded.
+
+use crate::http::Reply;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::{cell::RefCell, fs::File};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Interaction {
+    pub method: String,
+    pub url: String,
+    /// Request body with credentials redacted, for reference only.
+    #[serde(default)]
+    pub request: Option<String>,
+    pub status: u16,
+    pub response: String,
+}
+
+enum Mode {
+    Record(String),
+    Replay(Vec<bool>),
+}
+
+struct Cassette {
+    mode: Mode,
+    interactions: Vec<Interaction>,
+}
+
+thread_local! {
+    // per thread, so that tests replaying different cassettes don't mix up
+    static CASSETTE: RefCell<Option<Cassette>> = const { RefCell::new(None) };
+}
+
+/// Records every following request into `path`, overwriting it.
+pub fn record(path: &str) -> Result<(), String> {
+    let cassette = Cassette {
+        mode: Mode::Record(path.to_string()),
+        interactions: Vec::new(),
+    };
+    cassette.save()?;
+    CASSETTE.with(|c| *c.borrow_mut() = Some(cassette));
+    Ok(())
+}
+
+/// Answers every following request from the cassette at `path`.
+pub fn replay(path: &str) -> Result<(), String> {
+    let f = File::open(path).map_err(|e| format!("Can't open cassette {}: {}", path, e))?;
+    let interactions: Vec<Interaction> =
+        serde_yaml::from_reader(f).map_err(|e| format!("Invalid cassette {}: {}", path, e))?;
+    CASSETTE.with(|c| {
+        *c.borrow_mut() = Some(Cassette {
+            mode: Mode::Replay(vec![false; interactions.len()]),
+            interactions,
+        })
+    });
+    Ok(())
+}
+
+/// Whether recording or replaying.
+pub fn active() -> bool {
+    CASSETTE.with(|c| c.borrow().is_some())
+}
+
+/// When replaying, the recorded response to this request: `None` if not
+/// replaying, `Some(Err(..))` if the request was never recorded.
+pub fn lookup(method: &str, url: &str) -> Option<Result<Reply, String>> {
+    CASSETTE.with(|c| {
+        let mut c = c.borrow_mut();
+        let cassette = c.as_mut()?;
+        let used = match &mut cassette.mode {
+            Mode::Replay(used) => used,
+            Mode::Record(_) => return None,
+        };
+        let found = cassette
+            .interactions
+            .iter()
+            .enumerate()
+            .find(|(i, x)| !used[*i] && x.method == method && x.url == url);
+        Some(match found {
+            Some((i, x)) => {
+                used[i] = true;
+                Ok(Reply {
+                    status: StatusCode::from_u16(x.status).unwrap_or(StatusCode::OK),
+                    body: x.response.clone(),
+                })
+            }
+            None => Err(format!("no recorded response for {} {}", method, url)),
+        })
+    })
+}
+
+/// When recording, adds this interaction to the cassette.
+pub fn add(interaction: Interaction) -> Result<(), String> {
+    CASSETTE.with(|c| match c.borrow_mut().as_mut() {
+        Some(cassette) if matches!(cassette.mode, Mode::Record(_)) => {
+            cassette.interactions.push(interaction);
+            cassette.save()
+        }
+        _ => Ok(()),
+    })
+}
+
+impl Cassette {
+    // saved after every request, the shell leaves through exit()
+    fn save(&self) -> Result<(), String> {
+        if let Mode::Record(path) = &self.mode {
+            let f =
+                File::create(path).map_err(|e| format!("Can't write cassette {}: {}", path, e))?;
+            serde_yaml::to_writer(f, &self.interactions)
+                .map_err(|e| format!("Can't write cassette {}: {}", path, e))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::blocking::Client;
+    use std::{env, process};
+
+    #[test]
+    fn replays_a_recorded_session() {
+        replay("tests/cassettes/admin.yaml").unwrap();
+        let client = Client::new();
+
+        assert_eq!(
+            crate::login(&client, "mr", "robot"),
+            Ok("/GamifyUser/admin".to_string())
+        );
+        assert_eq!(crate::list(&client, "0", "100", true).unwrap().len(), 2);
+        let users = crate::inspect(&client, "1", false).unwrap();
+        let names: Vec<&str> = users.iter().map(|u| u.username.as_str()).collect();
+        assert_eq!(names, ["alice", "bob"]);
+        assert!(crate::inspect(&client, "1", true).unwrap().is_empty());
+        assert!(crate::delete(&client, "2"));
+        assert_eq!(crate::list(&client, "0", "100", true).unwrap().len(), 1);
+        // recorded as a 500 the second time
+        assert!(!crate::delete(&client, "2"));
+    }
+
+    #[test]
+    fn unrecorded_requests_fail() {
+        replay("tests/cassettes/admin.yaml").unwrap();
+        assert!(crate::inspect(&Client::new(), "42", false).is_err());
+    }
+
+    #[test]
+    fn records_then_replays_in_order() {
+        let path = env::temp_dir().join(format!("gamify-cassette-{}.yaml", process::id()));
+        let path = path.to_str().unwrap();
+        let interaction = |response: &str| Interaction {
+            method: "GET".to_string(),
+            url: "admin/listQuestionnaires?start=0".to_string(),
+            request: None,
+            status: 200,
+            response: response.to_string(),
+        };
+
+        record(path).unwrap();
+        add(interaction("first")).unwrap();
+        add(interaction("second")).unwrap();
+        replay(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let body = || {
+            lookup("GET", "admin/listQuestionnaires?start=0")
+                .unwrap()
+                .map(|r| r.body)
+        };
+        assert_eq!(body(), Ok("first".to_string()));
+        assert_eq!(body(), Ok("second".to_string()));
+        assert!(body().is_err());
+        assert!(lookup("POST", "CheckLogin").unwrap().is_err());
+    }
+}
@@ -0,0 +1,261 @@
+//! The `clone` command: copies a questionnaire to another date, or to a
+//! series of dates.
+//!
+//! The server doesn't tell the questions of a questionnaire, so they are
+//! gathered from the answers of the users who completed it.
+
+use crate::{
+    answers, arg_or_input, create_questionnaire, download_image, inspect, lookup_questionnaire,
+    parse_date, progress, prompt, theme::Paint, MAX_QUESTIONS,
+};
+use chrono::{Datelike, Duration, NaiveDate};
+use clap::ArgMatches;
+use log::warn;
+use reqwest::blocking::Client;
+use std::fs;
+
+/// More would rather be a typo in --until.
+const MAX_COPIES: usize = 366;
+/// Users whose answers are looked at for the questions.
+const ANSWERS_SAMPLE: usize = 5;
+
+pub fn command(client: &Client, args: &ArgMatches) -> bool {
+    let id = match arg_or_input(args, "id", "Questionnaire ID", None) {
+        Some(id) => id,
+        None => return false,
+    };
+    let source = match lookup_questionnaire(client, &id) {
+        Ok(q) => q,
+        Err(e) => {
+            println!("{}", e.error());
+            return false;
+        }
+    };
+    let id = source.questionnaireId.to_string();
+
+    let first = match arg_or_input(args, "date", "Date (YYYY-MM-DD)", None) {
+        Some(d) => d,
+        None => return false,
+    };
+    let dates = parse_date(&first).and_then(|first| {
+        match (args.value_of("every"), args.value_of("until")) {
+            (Some(every), Some(until)) => series(first, every, parse_date(until)?),
+            (Some(_), None) => Err("--every needs an --until date.".to_string()),
+            _ => Ok(vec![first]),
+        }
+    });
+    let dates = match dates {
+        Ok(d) => d,
+        Err(e) => {
+            println!("{}", e.error());
+            return false;
+        }
+    };
+
+    let name = args.value_of("name").unwrap_or(&source.name).to_string();
+    let questions = match args.values_of("question") {
+        Some(q) => q.map(String::from).collect(),
+        None => match questions(client, &id) {
+            Ok(q) if q.is_empty() => {
+                println!(
+                    "{}",
+                    format!(
+                        "Nobody answered questionnaire {}, so its questions are unknown: give them with -q.",
+                        id
+                    )
+                    .error()
+                );
+                return false;
+            }
+            Ok(q) => q,
+            Err(()) => {
+                println!("{}", "Can't get the questions.".error());
+                return false;
+            }
+        },
+    };
+    if questions.len() > MAX_QUESTIONS {
+        let e = if args.is_present("question") {
+            format!("At most {} questions, please.", MAX_QUESTIONS)
+        } else {
+            format!(
+                "The answers to {} have {} questions, more than a questionnaire takes: give at most {} with -q.",
+                id,
+                questions.len(),
+                MAX_QUESTIONS
+            )
+        };
+        println!("{}", e.error());
+        return false;
+    }
+
+    if dates.len() > 1 {
+        let prompt = format!(
+            "Create {} questionnaires, from {} to {}?",
+            dates.len(),
+            dates[0],
+            dates[dates.len() - 1]
+        );
+        if !prompt::confirm(&prompt, true) {
+            return true;
+        }
+    }
+
+    let image = match download_image(client, &source.image) {
+        Ok(p) => p,
+        Err(e) => {
+            println!("{}", e.error());
+            return false;
+        }
+    };
+    let mut summary = progress::Summary::new(dates.len());
+    let mut bar = progress::Progress::items("Cloning", dates.len() as u64);
+    for date in &dates {
+        if progress::cancelled() {
+            break;
+        }
+        if create_questionnaire(
+            client,
+            name.clone(),
+            date.to_string(),
+            image.to_string_lossy().into_owned(),
+            questions.clone(),
+        ) {
+            summary.done += 1;
+        } else {
+            println!("{}", format!("Can't create the copy of {}.", date).error());
+            summary.failed += 1;
+        }
+        bar.add(1);
+    }
+    drop(bar);
+    let _ = fs::remove_file(&image);
+
+    let ok = summary.done == summary.total;
+    let text = match dates.as_slice() {
+        [date] if ok => format!("OK! Questionnaire {} cloned to {}.", id, date),
+        _ => summary.describe("questionnaires created"),
+    };
+    if ok {
+        print!("{}", text.success());
+    } else {
+        print!("{}", text.error());
+    }
+    ok
+}
+
+/// Dates from `first` to `until`, one `every` day, week or month.
+fn series(first: NaiveDate, every: &str, until: NaiveDate) -> Result<Vec<NaiveDate>, String> {
+    if until < first {
+        return Err(format!("{} comes before {}.", until, first));
+    }
+    let mut dates = Vec::new();
+    for n in 0.. {
+        let date = match every {
+            "day" => first + Duration::days(n),
+            "week" => first + Duration::weeks(n),
+            "month" => match add_months(first, n as u32) {
+                Some(d) => d,
+                None => break,
+            },
+            e => return Err(format!("Can't repeat every {}.", e)),
+        };
+        if date > until {
+            break;
+        }
+        if dates.len() == MAX_COPIES {
+            return Err(format!("That's more than {} questionnaires.", MAX_COPIES));
+        }
+        dates.push(date);
+    }
+    Ok(dates)
+}
+
+/// `date` `n` months later, on the last day of the month if it's shorter.
+fn add_months(date: NaiveDate, n: u32) -> Option<NaiveDate> {
+    let months = date.year() * 12 + (date.month0() + n) as i32;
+    let (year, month) = (months / 12, months % 12 + 1);
+    (1..=date.day())
+        .rev()
+        .find_map(|day| NaiveDate::from_ymd_opt(year, month as u32, day))
+}
+
+/// Questions of questionnaire `id`, as found in the answers to it. Users
+/// whose answers can't be read are skipped.
+fn questions(client: &Client, id: &str) -> Result<Vec<String>, ()> {
+    let users = inspect(client, id, false)?;
+    let mut questions: Vec<String> = Vec::new();
+    let mut read = 0;
+    for user in &users {
+        if read == ANSWERS_SAMPLE {
+            break;
+        }
+        let list = match answers(client, id, user.userId) {
+            Ok(l) => l,
+            Err(()) => {
+                warn!("Can't get the answers of {} to {}", user.username, id);
+                continue;
+            }
+        };
+        read += 1;
+        for answer in list.opt {
+            if !questions.contains(&answer.question) {
+                questions.push(answer.question);
+            }
+        }
+    }
+    if read == 0 && !users.is_empty() {
+        return Err(());
+    }
+    Ok(questions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        http,
+        stub::{self, ANNA, BOB},
+    };
+
+    fn date(s: &str) -> NaiveDate {
+        parse_date(s).unwrap()
+    }
+
+    #[test]
+    fn schedules_series() {
+        let weekly = series(date("2021-01-10"), "week", date("2021-01-31")).unwrap();
+        assert_eq!(weekly.len(), 4);
+        assert_eq!(weekly[3], date("2021-01-31"));
+
+        let monthly = series(date("2021-01-31"), "month", date("2021-04-30")).unwrap();
+        assert_eq!(
+            monthly,
+            ["2021-01-31", "2021-02-28", "2021-03-31", "2021-04-30"]
+                .iter()
+                .map(|d| date(d))
+                .collect::<Vec<_>>()
+        );
+
+        assert!(series(date("2021-01-10"), "week", date("2021-01-01")).is_err());
+        assert!(series(date("2021-01-10"), "day", date("2031-01-01")).is_err());
+        assert!(parse_date("10/01/2021").is_err());
+    }
+
+    #[test]
+    fn gathers_the_questions_from_the_answers() {
+        let client = http::client().unwrap();
+        // carol's answers aren't there
+        let carol = r#"{"userId":3,"birth":"2001-02-03","sex":"F","username":"carol"}"#;
+        stub::users(501, false, &[carol, ANNA, BOB]);
+        stub::answers(501, 1, &[("Topping?", "Basil")]);
+        stub::answers(501, 2, &[("Topping?", "Ham"), ("Crust?", "Thin")]);
+        assert_eq!(
+            questions(&client, "501"),
+            Ok(vec!["Topping?".to_string(), "Crust?".to_string()])
+        );
+
+        stub::users(502, false, &[carol]);
+        assert_eq!(questions(&client, "502"), Err(()));
+    }
+}
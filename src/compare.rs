@@ -0,0 +1,375 @@
+//! The `compare` command: the answers of two users to a questionnaire side
+//! by side, or how two questionnaires were answered: how many completed or
+//! canceled them, and who by age, sex and expertise.
+
+use crate::{
+    all_questionnaires, answers, inspect, progress, prompt, table,
+    table::{Align, Cell, Column, Table},
+    theme::Paint,
+    users_table, AnswerList, User,
+};
+use clap::ArgMatches;
+use reqwest::blocking::Client;
+use std::collections::BTreeMap;
+
+/// The statistical answers, in the order the server sends them.
+const STATS: [&str; 3] = ["Age", "Sex", "Expertise"];
+
+/// How a questionnaire was answered.
+#[derive(Debug, Default, PartialEq)]
+struct Profile {
+    completed: usize,
+    canceled: usize,
+    /// Answers read, out of the completed ones.
+    read: usize,
+    /// Users by statistical answer, as (index in STATS, group).
+    counts: BTreeMap<(usize, String), usize>,
+}
+
+pub fn command(client: &Client, args: &ArgMatches) -> bool {
+    let ids: Vec<String> = match args.values_of("id") {
+        Some(ids) => ids.map(String::from).collect(),
+        None => match prompt::input("Questionnaire IDs (one to compare users)", None) {
+            Some(ids) => ids.split_whitespace().map(String::from).collect(),
+            None => return false,
+        },
+    };
+    match (ids.as_slice(), args.values_of("users")) {
+        ([id], users) => compare_users(client, id, users.map(|u| u.collect()).unwrap_or_default()),
+        ([a, b], None) => compare_questionnaires(client, a, b),
+        ([_, _], Some(_)) => {
+            println!("{}", "Users are compared on one questionnaire.".error());
+            false
+        }
+        _ => {
+            println!("{}", "Give one questionnaire, or two.".error());
+            false
+        }
+    }
+}
+
+fn compare_users(client: &Client, id: &str, wanted: Vec<&str>) -> bool {
+    let users = match inspect(client, id, false) {
+        Ok(u) => u,
+        Err(()) => {
+            println!("{}", format!("Can't get the users of {}.", id).error());
+            return false;
+        }
+    };
+    let picked = match wanted.as_slice() {
+        [a, b] => [find(&users, a), find(&users, b)],
+        [] => [pick(&users, "First user"), pick(&users, "Second user")],
+        _ => {
+            println!("{}", "Give two users to compare.".error());
+            return false;
+        }
+    };
+    let (a, b) = match picked {
+        [Ok(a), Ok(b)] => (a, b),
+        [Err(e), _] | [_, Err(e)] => {
+            println!("{}", e.error());
+            return false;
+        }
+    };
+
+    let lists = (answers(client, id, a.userId), answers(client, id, b.userId));
+    match lists {
+        (Ok(x), Ok(y)) => {
+            answers_table(&a.username, &x, &b.username, &y).print();
+            true
+        }
+        _ => {
+            println!("{}", "Error retrieving answers.".error());
+            false
+        }
+    }
+}
+
+/// The user with id or username `wanted`, among those who completed it.
+fn find<'u>(users: &'u [User], wanted: &str) -> Result<&'u User, String> {
+    users
+        .iter()
+        .find(|u| u.userId.to_string() == wanted || u.username == wanted)
+        .ok_or_else(|| format!("{} didn't complete this questionnaire.", wanted))
+}
+
+fn pick<'u>(users: &'u [User], prompt: &str) -> Result<&'u User, String> {
+    let table = users_table(users);
+    let width = table::width().saturating_sub(3);
+    let header = format!("{}\n  {}", prompt, table.header(width));
+    prompt::select(&header, &table.rows(width))
+        .map(|i| &users[table.order()[i]])
+        .ok_or_else(|| "Give two users to compare, with --users.".to_string())
+}
+
+/// Both users' answers, a row per question; the answers they agree on are
+/// muted, to make the others stand out.
+fn answers_table(a: &str, x: &AnswerList, b: &str, y: &AnswerList) -> Table {
+    let mut table = Table::new(vec![
+        Column::new("Question")
+            .align(Align::Left)
+            .wrap()
+            .style(|s| s.question()),
+        Column::new(a).align(Align::Left).wrap(),
+        Column::new(b).align(Align::Left).wrap(),
+    ]);
+    let stat = |list: &AnswerList, i: usize| {
+        list.stats
+            .get(i)
+            .cloned()
+            .flatten()
+            .unwrap_or_else(|| "N/A".to_string())
+    };
+    let mut rows: Vec<(String, String, String)> = STATS
+        .iter()
+        .enumerate()
+        .map(|(i, name)| (name.to_string(), stat(x, i), stat(y, i)))
+        .collect();
+
+    let mut questions: Vec<&str> = x.opt.iter().map(|o| o.question.as_str()).collect();
+    for o in &y.opt {
+        if !questions.contains(&o.question.as_str()) {
+            questions.push(&o.question);
+        }
+    }
+    let content = |list: &AnswerList, q: &str| {
+        list.opt
+            .iter()
+            .find(|o| o.question == q)
+            .map_or("—".to_string(), |o| o.content.clone())
+    };
+    for q in questions {
+        rows.push((q.to_string(), content(x, q), content(y, q)));
+    }
+
+    for (question, first, second) in rows {
+        let (first, second) = if first == second {
+            (first.muted(), second.muted())
+        } else {
+            (first.answer(), second.answer())
+        };
+        table.add_row(vec![question.into(), first.into(), second.into()]);
+    }
+    table
+}
+
+fn compare_questionnaires(client: &Client, a: &str, b: &str) -> bool {
+    let profiles = (profile(client, a), profile(client, b));
+    let (x, y) = match profiles {
+        (Ok(x), Ok(y)) => (x, y),
+        _ => {
+            println!("{}", "Can't get the users of the questionnaires.".error());
+            return false;
+        }
+    };
+    // named if possible, ids only mean something to the admin
+    let questionnaires = all_questionnaires(client).unwrap_or_default();
+    let title = |id: &str| match questionnaires
+        .iter()
+        .find(|q| q.questionnaireId.to_string() == id)
+    {
+        Some(q) => format!("#{} {}", id, q.name),
+        None => format!("#{}", id),
+    };
+    profiles_table(&title(a), &x, &title(b), &y).print();
+    if x.read < x.completed || y.read < y.completed {
+        println!(
+            "{}",
+            "Cancelled: the answers were only partly read.".warning()
+        );
+    }
+    true
+}
+
+/// Who completed or canceled questionnaire `id`, and what they answered to
+/// the statistical questions.
+fn profile(client: &Client, id: &str) -> Result<Profile, ()> {
+    let completed = inspect(client, id, false)?;
+    let mut profile = Profile {
+        completed: completed.len(),
+        canceled: inspect(client, id, true)?.len(),
+        ..Profile::default()
+    };
+
+    let mut bar = progress::Progress::items(
+        &format!("Reading answers to #{}", id),
+        completed.len() as u64,
+    );
+    for user in &completed {
+        if progress::cancelled() {
+            break;
+        }
+        let list = answers(client, id, user.userId)?;
+        for i in 0..STATS.len() {
+            let answer = list.stats.get(i).cloned().flatten();
+            *profile.counts.entry((i, group(i, answer))).or_insert(0) += 1;
+        }
+        profile.read += 1;
+        bar.add(1);
+    }
+    Ok(profile)
+}
+
+/// The group of a statistical answer: the age range for ages.
+fn group(stat: usize, answer: Option<String>) -> String {
+    let answer = match answer {
+        Some(a) => a,
+        None => return "N/A".to_string(),
+    };
+    if stat != 0 {
+        return answer;
+    }
+    // ranges sort as text, as they're kept
+    match answer.trim().parse::<u32>() {
+        Ok(0..=17) => "0-17",
+        Ok(18..=24) => "18-24",
+        Ok(25..=34) => "25-34",
+        Ok(35..=44) => "35-44",
+        Ok(45..=54) => "45-54",
+        Ok(_) => "55+",
+        Err(_) => "N/A",
+    }
+    .to_string()
+}
+
+/// Counts and shares of both questionnaires, a row per group of users.
+fn profiles_table(a: &str, x: &Profile, b: &str, y: &Profile) -> Table {
+    let mut table = Table::new(vec![
+        Column::new("Users").align(Align::Left),
+        Column::new(a).align(Align::Right),
+        Column::new(b).align(Align::Right),
+    ]);
+    let rate = |p: &Profile| share(p.canceled, p.completed + p.canceled);
+    table.add_row(vec![
+        "Completed".into(),
+        x.completed.into(),
+        y.completed.into(),
+    ]);
+    table.add_row(vec![
+        "Canceled".into(),
+        x.canceled.into(),
+        y.canceled.into(),
+    ]);
+    table.add_row(vec![
+        "Cancellation rate".into(),
+        rate(x).into(),
+        rate(y).into(),
+    ]);
+
+    let mut groups: Vec<&(usize, String)> = x.counts.keys().collect();
+    for g in y.counts.keys() {
+        if !groups.contains(&g) {
+            groups.push(g);
+        }
+    }
+    groups.sort();
+    let cell = |p: &Profile, g: &(usize, String)| {
+        let n = p.counts.get(g).copied().unwrap_or(0);
+        format!("{} ({})", n, share(n, p.read))
+    };
+    for g in groups {
+        table.add_row(vec![
+            format!("{} {}", STATS[g.0], g.1).stat().into(),
+            Cell::from(cell(x, g)),
+            Cell::from(cell(y, g)),
+        ]);
+    }
+    table
+}
+
+fn share(n: usize, total: usize) -> String {
+    match total {
+        0 => "-".to_string(),
+        t => format!("{:.0}%", n as f64 * 100.0 / t as f64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        http,
+        stub::{self, ANNA, BOB},
+        OptionalAnswer,
+    };
+
+    fn list(stats: [Option<&str>; 3], opt: &[(&str, &str)]) -> AnswerList {
+        AnswerList {
+            stats: stats.iter().map(|s| s.map(String::from)).collect(),
+            opt: opt
+                .iter()
+                .map(|(q, c)| OptionalAnswer {
+                    question: q.to_string(),
+                    content: c.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn puts_answers_side_by_side() {
+        let x = list(
+            [Some("23"), Some("F"), None],
+            &[("Topping?", "Basil"), ("Crust?", "Thin")],
+        );
+        let y = list(
+            [Some("40"), Some("F"), Some("HIGH")],
+            &[("Topping?", "Ham"), ("Drink?", "Beer")],
+        );
+        let rendered: Vec<String> = answers_table("anna", &x, "bob", &y)
+            .render(200)
+            .iter()
+            .map(|l| table::strip_ansi(l))
+            .collect();
+        let rows: Vec<Vec<&str>> = rendered
+            .iter()
+            .filter(|l| l.starts_with('│'))
+            .map(|l| l.trim_matches('│').split('│').map(str::trim).collect())
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                vec!["Age", "23", "40"],
+                vec!["Sex", "F", "F"],
+                vec!["Expertise", "N/A", "HIGH"],
+                vec!["Topping?", "Basil", "Ham"],
+                vec!["Crust?", "Thin", "—"],
+                vec!["Drink?", "—", "Beer"],
+            ]
+        );
+    }
+
+    #[test]
+    fn groups_ages_in_ranges() {
+        assert_eq!(group(0, Some("17".to_string())), "0-17");
+        assert_eq!(group(0, Some("25".to_string())), "25-34");
+        assert_eq!(group(0, Some("80".to_string())), "55+");
+        assert_eq!(group(0, Some("old".to_string())), "N/A");
+        assert_eq!(group(1, Some("F".to_string())), "F");
+        assert_eq!(group(2, None), "N/A");
+    }
+
+    #[test]
+    fn profiles_questionnaires() {
+        let client = http::client().unwrap();
+        stub::users(601, false, &[ANNA, BOB]);
+        stub::users(
+            601,
+            true,
+            &[r#"{"userId":3,"birth":"2001-02-03","sex":"F","username":"carol"}"#],
+        );
+        let answers = |user, stats| stub::on("GET", &stub::answers_url(601, user), 200, stats);
+        answers(1, r#"{"stats":["23","F","LOW"],"opt":[]}"#);
+        answers(2, r#"{"stats":["40","M",null],"opt":[]}"#);
+
+        let p = profile(&client, "601").unwrap();
+        assert_eq!((p.completed, p.canceled, p.read), (2, 1, 2));
+        let count = |stat: usize, group: &str| p.counts.get(&(stat, group.to_string())).copied();
+        assert_eq!(count(0, "18-24"), Some(1));
+        assert_eq!(count(0, "35-44"), Some(1));
+        assert_eq!(count(1, "F"), Some(1));
+        assert_eq!(count(2, "N/A"), Some(1));
+        assert_eq!(share(1, 3), "33%");
+        assert_eq!(share(0, 0), "-");
+    }
+}
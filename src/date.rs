@@ -0,0 +1,181 @@
+//! Dates sent by the server: parsing whatever format it uses, and rendering
+//! them with the `date_format` and `locale` from the config.
+//!
+//! Jackson writes dates either as `Jan 10, 2021` (possibly followed by the
+//! time) or as epoch milliseconds, ISO-8601 is accepted too.
+
+use crate::CONFIG;
+use chrono::{DateTime, Datelike, Local, Locale, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use lazy_static::lazy_static;
+use log::warn;
+use serde::{de, Deserializer, Serializer};
+use std::{convert::TryFrom, fmt, fmt::Write};
+
+/// How Jackson writes dates, and how they are sent back.
+pub const JACKSON: &str = "%b %-d, %Y";
+/// Rendering when the config sets none.
+pub const DEFAULT_FORMAT: &str = "%b %-d %Y";
+
+lazy_static! {
+    static ref LOCALE: Locale = match &CONFIG.locale {
+        Some(l) => Locale::try_from(l.as_str()).unwrap_or_else(|_| {
+            warn!("Unknown locale {}, using the default one.", l);
+            Locale::POSIX
+        }),
+        None => Locale::POSIX,
+    };
+}
+
+/// Parses a date as the server may send it.
+pub fn parse(s: &str) -> Option<NaiveDate> {
+    let s = s.trim();
+    if let Ok(ms) = s.parse::<i64>() {
+        return from_millis(ms);
+    }
+    if let Ok(d) = DateTime::parse_from_rfc3339(s) {
+        return Some(d.naive_local().date());
+    }
+    if let Ok(d) = NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f") {
+        return Some(d.date());
+    }
+    if let Ok(d) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return Some(d);
+    }
+    // Jackson, ignoring the time after the year if any
+    let words: Vec<&str> = s.split_whitespace().take(3).collect();
+    NaiveDate::parse_from_str(words.join(" ").trim_end_matches(','), "%b %d, %Y").ok()
+}
+
+/// The local date at epoch milliseconds `ms`.
+fn from_millis(ms: i64) -> Option<NaiveDate> {
+    Local
+        .timestamp_millis_opt(ms)
+        .single()
+        .map(|d| d.naive_local().date())
+}
+
+/// Renders `date` in the configured format and locale.
+pub fn format(date: NaiveDate) -> String {
+    let date = Utc.from_utc_date(&date);
+    let mut s = String::new();
+    if write!(s, "{}", date.format_localized(&CONFIG.date_format, *LOCALE)).is_err() {
+        // invalid date_format
+        s.clear();
+        let _ = write!(s, "{}", date.format_localized(DEFAULT_FORMAT, *LOCALE));
+    }
+    s
+}
+
+/// Renders `date` with the strftime format `fmt`, in the configured locale.
+pub fn localized(date: NaiveDate, fmt: &str) -> String {
+    Utc.from_utc_date(&date)
+        .format_localized(fmt, *LOCALE)
+        .to_string()
+}
+
+/// Age on `day` of someone born on `birth`.
+pub fn age(birth: NaiveDate, day: NaiveDate) -> i32 {
+    let mut age = day.year() - birth.year();
+    if (day.month(), day.day()) < (birth.month(), birth.day()) {
+        age -= 1;
+    }
+    age
+}
+
+pub fn today() -> NaiveDate {
+    Local::today().naive_local()
+}
+
+/// For `#[serde(with = "date")]`.
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NaiveDate, D::Error> {
+    deserializer.deserialize_any(Visitor)
+}
+
+/// For `#[serde(with = "date")]`, in the Jackson format.
+pub fn serialize<S: Serializer>(date: &NaiveDate, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&date.format(JACKSON))
+}
+
+struct Visitor;
+
+impl<'de> de::Visitor<'de> for Visitor {
+    type Value = NaiveDate;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a date like \"Jan 10, 2021\", \"2021-01-10\" or epoch milliseconds")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<NaiveDate, E> {
+        parse(v).ok_or_else(|| E::invalid_value(de::Unexpected::Str(v), &self))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<NaiveDate, E> {
+        from_millis(v).ok_or_else(|| E::invalid_value(de::Unexpected::Signed(v), &self))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<NaiveDate, E> {
+        self.visit_i64(v as i64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ymd(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd(y, m, d)
+    }
+
+    #[test]
+    fn parses_the_server_formats() {
+        let day = Some(ymd(2021, 1, 10));
+        assert_eq!(parse("Jan 10, 2021"), day);
+        assert_eq!(parse("Jan 10, 2021, 12:00:00 AM"), day);
+        assert_eq!(parse("Jan 10, 2021 12:00:00 AM"), day);
+        assert_eq!(parse("2021-01-10"), day);
+        assert_eq!(parse("2021-01-10T00:00:00"), day);
+        assert_eq!(parse("2021-01-10T23:30:00+01:00"), day);
+        let noon = Local.ymd(2021, 1, 10).and_hms(12, 0, 0).timestamp_millis();
+        assert_eq!(parse(&noon.to_string()), day);
+        assert_eq!(parse("Jan 10 2021"), None);
+        assert_eq!(parse("yesterday"), None);
+        assert_eq!(parse(""), None);
+    }
+
+    #[test]
+    fn deserializes_strings_and_numbers() {
+        #[derive(serde::Deserialize, serde::Serialize)]
+        struct D {
+            #[serde(with = "super")]
+            d: NaiveDate,
+        }
+        let noon = Local.ymd(1990, 3, 3).and_hms(12, 0, 0).timestamp_millis();
+        for json in [
+            r#"{"d":"Mar 3, 1990"}"#.to_string(),
+            r#"{"d":"1990-03-03"}"#.to_string(),
+            format!(r#"{{"d":{}}}"#, noon),
+        ] {
+            let d: D = serde_json::from_str(&json).unwrap();
+            assert_eq!(d.d, ymd(1990, 3, 3));
+        }
+        assert!(serde_json::from_str::<D>(r#"{"d":"3/3/1990"}"#).is_err());
+        assert!(serde_json::from_str::<D>(r#"{"d":null}"#).is_err());
+
+        let d = D { d: ymd(1990, 3, 3) };
+        assert_eq!(serde_json::to_string(&d).unwrap(), r#"{"d":"Mar 3, 1990"}"#);
+    }
+
+    #[test]
+    fn renders_in_the_default_format() {
+        assert_eq!(format(ymd(2021, 1, 10)), "Jan 10 2021");
+    }
+
+    #[test]
+    fn computes_ages() {
+        let birth = ymd(1990, 3, 3);
+        assert_eq!(age(birth, ymd(2021, 3, 2)), 30);
+        assert_eq!(age(birth, ymd(2021, 3, 3)), 31);
+        assert_eq!(age(birth, ymd(2021, 12, 31)), 31);
+        assert_eq!(age(ymd(2000, 2, 29), ymd(2021, 2, 28)), 20);
+    }
+}
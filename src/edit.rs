@@ -0,0 +1,507 @@
+//! The `edit` command: changes the name, date, image or questions of a
+//! questionnaire.
+//!
+//! Changes go through the update endpoint when the server has one. Without
+//! it, the questionnaire is deleted and created again under a new id, which
+//! is only done if nobody answered it yet and the admin confirms. The server
+//! never tells the questions of a questionnaire, so those have to be listed
+//! again to be recreated.
+
+use crate::{
+    all_questionnaires, ask_questions, create_questionnaire, delete, download_image, http,
+    image_part, inspect, lookup_questionnaire, parse_date, prompt, questionnaire_form,
+    theme::Paint, Questionnaire, CONFIG, ENDPOINT, MAX_QUESTIONS,
+};
+use clap::ArgMatches;
+use reqwest::{blocking::Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::{env, fs, path::Path, process};
+
+/// What can be edited, as shown in the editor.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Draft {
+    name: String,
+    date: String,
+    /// File name on the server, or path of a new image.
+    image: String,
+    #[serde(default)]
+    questions: Vec<String>,
+}
+
+impl Draft {
+    fn of(q: &Questionnaire) -> Draft {
+        Draft {
+            name: q.name.clone(),
+            date: q.datetime.format("%Y-%m-%d").to_string(),
+            image: q.image.clone(),
+            questions: Vec::new(),
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("The name can't be empty.".to_string());
+        }
+        parse_date(&self.date)?;
+        if self.questions.len() > MAX_QUESTIONS {
+            return Err(format!("At most {} questions, please.", MAX_QUESTIONS));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Line {
+    Removed(String),
+    Added(String),
+}
+
+#[derive(Debug, PartialEq)]
+enum Applied {
+    Updated,
+    /// Under a new id, if it could be found.
+    Recreated(Option<i32>),
+    /// The admin didn't want it recreated after all.
+    Kept,
+}
+
+pub fn command(client: &Client, args: &ArgMatches) -> bool {
+    let id = match args
+        .value_of("id")
+        .map(String::from)
+        .or_else(|| prompt::input("Questionnaire ID", None))
+    {
+        Some(id) => id,
+        None => return false,
+    };
+    let questionnaire = match lookup_questionnaire(client, &id) {
+        Ok(q) => q,
+        Err(e) => {
+            println!("{}", e.error());
+            return false;
+        }
+    };
+
+    let old = Draft::of(&questionnaire);
+    let given = ["name", "date", "picture", "question"]
+        .iter()
+        .any(|a| args.is_present(a));
+    let new = if given || !prompt::is_interactive() {
+        Some(from_args(&old, args))
+    } else {
+        match editor().filter(|_| !args.is_present("prompt")) {
+            Some(editor) => in_editor(&editor, &id, &old),
+            None => with_prompts(&old),
+        }
+    };
+    let new = match new {
+        Some(d) if d == old => {
+            println!("{}", "Nothing to change.".muted());
+            return true;
+        }
+        Some(d) => d,
+        None => {
+            println!("{}", "Edit cancelled.".muted());
+            return true;
+        }
+    };
+    if let Err(e) = new.validate() {
+        println!("{}", e.error());
+        return false;
+    }
+
+    for line in diff(&old, &new) {
+        match line {
+            Line::Removed(l) => println!("{}", format!("- {}", l).error()),
+            Line::Added(l) => println!("{}", format!("+ {}", l).success()),
+        }
+    }
+    if !prompt::confirm("Apply these changes?", true) {
+        println!("{}", "Edit cancelled.".muted());
+        return true;
+    }
+
+    match apply(client, &questionnaire, &old, &new) {
+        Ok(Applied::Updated) => {
+            print!("{}", format!("OK! Questionnaire {} updated.", id).success());
+            true
+        }
+        Ok(Applied::Recreated(Some(new_id))) => {
+            print!(
+                "{}",
+                format!("OK! Questionnaire {} recreated as {}.", id, new_id).success()
+            );
+            true
+        }
+        Ok(Applied::Recreated(None)) => {
+            print!(
+                "{}",
+                format!("OK! Questionnaire {} recreated, under a new id.", id).success()
+            );
+            true
+        }
+        Ok(Applied::Kept) => {
+            println!("{}", "Edit cancelled.".muted());
+            true
+        }
+        Err(e) => {
+            print!("{}", e.error());
+            false
+        }
+    }
+}
+
+/// `draft` with the fields given on the command line.
+fn from_args(draft: &Draft, args: &ArgMatches) -> Draft {
+    let mut draft = draft.clone();
+    if let Some(n) = args.value_of("name") {
+        draft.name = n.to_string();
+    }
+    if let Some(d) = args.value_of("date") {
+        draft.date = d.to_string();
+    }
+    if let Some(p) = args.value_of("picture") {
+        draft.image = p.to_string();
+    }
+    if let Some(q) = args.values_of("question") {
+        draft.questions = q.map(String::from).collect();
+    }
+    draft
+}
+
+fn editor() -> Option<String> {
+    env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .ok()
+        .filter(|e| !e.trim().is_empty())
+}
+
+/// What the editor opens.
+fn file_content(id: &str, draft: &Draft) -> String {
+    format!(
+        "# Questionnaire {}: save and quit to apply the changes, empty the file to cancel.\n\
+         # image: keep it, or give the path of a new one.\n\
+         # questions: the server doesn't tell them; list them all to change them.\n{}",
+        id,
+        serde_yaml::to_string(draft).unwrap_or_default()
+    )
+}
+
+/// The draft in the edited file, `None` if it was emptied.
+fn parse(content: &str) -> Result<Option<Draft>, String> {
+    let blank = content
+        .lines()
+        .all(|l| l.trim().is_empty() || l.trim_start().starts_with('#'));
+    if blank {
+        return Ok(None);
+    }
+    serde_yaml::from_str(content)
+        .map(Some)
+        .map_err(|e| format!("Invalid questionnaire: {}", e))
+}
+
+/// Lets the user change `draft` in `editor`, as YAML.
+fn in_editor(editor: &str, id: &str, draft: &Draft) -> Option<Draft> {
+    let path = env::temp_dir().join(format!("gamify-edit-{}-{}.yaml", process::id(), id));
+    if let Err(e) = fs::write(&path, file_content(id, draft)) {
+        println!(
+            "{}",
+            format!("Can't write {}: {}", path.display(), e).error()
+        );
+        return None;
+    }
+
+    let result = loop {
+        // e.g. `code --wait`
+        let mut words = editor.split_whitespace();
+        let status = process::Command::new(words.next().unwrap_or_default())
+            .args(words)
+            .arg(&path)
+            .status();
+        if let Err(e) = status {
+            println!("{}", format!("Can't run {}: {}", editor, e).error());
+            break None;
+        }
+        let parsed = fs::read_to_string(&path)
+            .map_err(|e| format!("Can't read {}: {}", path.display(), e))
+            .and_then(|c| parse(&c));
+        match parsed {
+            Ok(d) => break d,
+            Err(e) => {
+                println!("{}", e.error());
+                if !prompt::confirm("Edit again?", true) {
+                    break None;
+                }
+            }
+        }
+    };
+    let _ = fs::remove_file(&path);
+    result
+}
+
+/// Asks for each field, the current value being the default.
+fn with_prompts(draft: &Draft) -> Option<Draft> {
+    let mut new = Draft {
+        name: prompt::input("Questionnaire name", Some(&draft.name))?,
+        date: prompt::input("Date (YYYY-MM-DD)", Some(&draft.date))?,
+        image: prompt::input("Image (path of a new one)", Some(&draft.image))?,
+        questions: draft.questions.clone(),
+    };
+    if prompt::confirm("Change the questions?", false) {
+        new.questions = ask_questions()?;
+    }
+    Some(new)
+}
+
+/// The fields of `old` that `new` changes.
+fn diff(old: &Draft, new: &Draft) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let fields = [
+        ("name", &old.name, &new.name),
+        ("date", &old.date, &new.date),
+        ("image", &old.image, &new.image),
+    ];
+    for (field, o, n) in &fields {
+        if o != n {
+            lines.push(Line::Removed(format!("{}: {}", field, o)));
+            lines.push(Line::Added(format!("{}: {}", field, n)));
+        }
+    }
+    for q in old.questions.iter().filter(|q| !new.questions.contains(q)) {
+        lines.push(Line::Removed(format!("question: {}", q)));
+    }
+    for q in new.questions.iter().filter(|q| !old.questions.contains(q)) {
+        lines.push(Line::Added(format!("question: {}", q)));
+    }
+    lines
+}
+
+fn apply(client: &Client, q: &Questionnaire, old: &Draft, new: &Draft) -> Result<Applied, String> {
+    let id = q.questionnaireId.to_string();
+    match update(client, &id, old, new)? {
+        true => Ok(Applied::Updated),
+        false => recreate(client, &id, old, new),
+    }
+}
+
+/// Sends the changes to the update endpoint; false if there's none.
+fn update(client: &Client, id: &str, old: &Draft, new: &Draft) -> Result<bool, String> {
+    // left out, the questions stay as they are
+    let questions = if new.questions != old.questions {
+        new.questions.clone()
+    } else {
+        Vec::new()
+    };
+    let mut form = questionnaire_form(new.name.clone(), new.date.clone(), questions);
+    if new.image != old.image {
+        form = form.part("image", image_part(&new.image)?);
+    }
+    let res = http::send(
+        client,
+        client
+            .post(&format!("{}{}", CONFIG.base_link, ENDPOINT::ADMIN_UPDATE))
+            .query(&[("id", id)])
+            .multipart(form),
+    )
+    .map_err(|e| format!("Update failed: {}", e))?;
+    // the questionnaire was just listed, so a 404 is the endpoint missing
+    match res.status {
+        StatusCode::OK => Ok(true),
+        StatusCode::NOT_FOUND => Ok(false),
+        s => Err(format!("Update failed: {}", s)),
+    }
+}
+
+/// Deletes the questionnaire and creates `new` in its place, if nobody
+/// answered it yet and the admin agrees.
+fn recreate(client: &Client, id: &str, old: &Draft, new: &Draft) -> Result<Applied, String> {
+    if new.questions.is_empty() {
+        return Err(
+            "The server can't update questionnaires, and doesn't tell their questions: \
+             list them all to recreate this one."
+                .to_string(),
+        );
+    }
+    let answers = |canceled| {
+        inspect(client, id, canceled)
+            .map(|users| users.len())
+            .map_err(|_| format!("Can't tell whether questionnaire {} was answered.", id))
+    };
+    let (completed, canceled) = (answers(false)?, answers(true)?);
+    if completed + canceled > 0 {
+        let users = completed + canceled;
+        return Err(format!(
+            "Questionnaire {} was answered by {} {} ({} canceled): it can't be recreated.",
+            id,
+            users,
+            if users == 1 { "user" } else { "users" },
+            canceled
+        ));
+    }
+    if !prompt::confirm(
+        &format!(
+            "The server can't update questionnaires: delete questionnaire {} and create it \
+             again under a new id?",
+            id
+        ),
+        true,
+    ) {
+        return Ok(Applied::Kept);
+    }
+
+    let downloaded = if new.image == old.image {
+        Some(download_image(client, &old.image)?)
+    } else if Path::new(&new.image).is_file() {
+        None
+    } else {
+        return Err(format!("Can't read {}.", new.image));
+    };
+    let image = match &downloaded {
+        Some(p) => p.to_string_lossy().into_owned(),
+        None => new.image.clone(),
+    };
+
+    let result = if !delete(client, id) {
+        Err(format!("Can't delete questionnaire {}.", id))
+    } else if !create_questionnaire(
+        client,
+        new.name.clone(),
+        new.date.clone(),
+        image,
+        new.questions.clone(),
+    ) {
+        // keep the edit, it would be lost along with the questionnaire
+        let saved = env::temp_dir().join(format!("gamify-edit-{}.yaml", id));
+        let _ = fs::write(&saved, file_content(id, new));
+        Err(format!(
+            "Questionnaire {} was deleted but couldn't be created again; the edit is in {}.",
+            id,
+            saved.display()
+        ))
+    } else {
+        Ok(Applied::Recreated(created_id(client, new)))
+    };
+    if let Some(p) = downloaded {
+        let _ = fs::remove_file(p);
+    }
+    result
+}
+
+/// The id of the newest questionnaire named and dated as `draft`, which the
+/// server doesn't reply with when creating it.
+fn created_id(client: &Client, draft: &Draft) -> Option<i32> {
+    let date = parse_date(&draft.date).ok()?;
+    all_questionnaires(client)
+        .ok()?
+        .iter()
+        .filter(|q| q.name == draft.name && q.datetime == date)
+        .map(|q| q.questionnaireId)
+        .max()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stub::{self, ANNA};
+
+    fn draft() -> Draft {
+        Draft {
+            name: "Pizza".to_string(),
+            date: "2021-01-10".to_string(),
+            image: "pizza.png".to_string(),
+            questions: Vec::new(),
+        }
+    }
+
+    fn questionnaire(id: i32) -> Questionnaire {
+        Questionnaire {
+            questionnaireId: id,
+            datetime: chrono::NaiveDate::from_ymd(2021, 1, 10),
+            image: "pizza.png".to_string(),
+            name: "Pizza".to_string(),
+        }
+    }
+
+    #[test]
+    fn diffs_the_changed_fields() {
+        let old = draft();
+        let mut new = draft();
+        assert!(diff(&old, &new).is_empty());
+        new.date = "2021-01-11".to_string();
+        new.questions = vec!["Topping?".to_string()];
+        assert_eq!(
+            diff(&old, &new),
+            vec![
+                Line::Removed("date: 2021-01-10".to_string()),
+                Line::Added("date: 2021-01-11".to_string()),
+                Line::Added("question: Topping?".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_back_the_edited_file() {
+        let mut d = draft();
+        assert_eq!(parse(&file_content("1", &d)), Ok(Some(d.clone())));
+        assert_eq!(parse("# nothing left\n\n"), Ok(None));
+        assert!(parse("name: [").is_err());
+
+        assert!(d.validate().is_ok());
+        d.date = "10/01/2021".to_string();
+        assert!(d.validate().unwrap_err().contains("YYYY-MM-DD"));
+    }
+
+    #[test]
+    fn updates_when_the_server_can() {
+        let client = http::client().unwrap();
+        let url = "admin/update?id=401";
+        stub::on("POST", url, 200, "");
+        let mut new = draft();
+        new.name = "Pasta".to_string();
+        assert_eq!(
+            apply(&client, &questionnaire(401), &draft(), &new),
+            Ok(Applied::Updated)
+        );
+        let body = stub::body("POST", url).unwrap();
+        assert!(body.contains("Pasta") && !body.contains("Question0"));
+
+        new.questions = vec!["Topping?".to_string()];
+        apply(&client, &questionnaire(401), &draft(), &new).unwrap();
+        assert!(stub::body("POST", url).unwrap().contains("Topping?"));
+    }
+
+    #[test]
+    fn recreates_only_unanswered_questionnaires() {
+        let client = http::client().unwrap();
+        stub::users(402, false, &[ANNA]);
+        stub::users(402, true, &[]);
+        let mut new = draft();
+        new.questions = vec!["Topping?".to_string()];
+
+        let e = apply(&client, &questionnaire(402), &draft(), &new).unwrap_err();
+        assert!(e.contains("answered by 1 user ("));
+        assert_eq!(stub::hits("DELETE", "admin/delete?id=402"), 0);
+
+        // without the questions there's nothing to recreate it from
+        let e = apply(&client, &questionnaire(403), &draft(), &draft()).unwrap_err();
+        assert!(e.contains("list them all"));
+        assert_eq!(stub::hits("GET", &stub::users_url(403, false)), 0);
+    }
+
+    #[test]
+    fn finds_the_recreated_questionnaire() {
+        let client = http::client().unwrap();
+        stub::on(
+            "GET",
+            "admin/listQuestionnaires?start=0&size=100&past=false",
+            200,
+            r#"[{"questionnaireId":404,"datetime":"2021-01-10","image":"a.png","name":"Pizza"},
+                {"questionnaireId":405,"datetime":"2021-01-11","image":"a.png","name":"Pizza"},
+                {"questionnaireId":406,"datetime":"2021-01-10","image":"a.png","name":"Pizza"}]"#,
+        );
+        assert_eq!(created_id(&client, &draft()), Some(406));
+        let mut other = draft();
+        other.name = "Sushi".to_string();
+        assert_eq!(created_id(&client, &other), None);
+    }
+}
@@ -0,0 +1,157 @@
+//! Shell history: where it's kept, what stays out of it and `!` expansion.
+
+use crate::theme::Paint;
+use crate::CONFIG;
+use clap::ArgMatches;
+use log::debug;
+use rustyline::history::History;
+use std::path::{Path, PathBuf};
+
+/// History file used before profiles, read when the profile has none yet.
+const LEGACY_FILE: &str = ".gamify_history.txt";
+
+/// History file of the current profile: `history_file` from the config if
+/// set, `<data dir>/gamify-rust/history/<profile>.txt` otherwise.
+pub fn path() -> Option<PathBuf> {
+    if let Some(f) = &CONFIG.history_file {
+        return Some(PathBuf::from(f));
+    }
+    dirs::data_dir().map(|d| {
+        d.join("gamify-rust")
+            .join("history")
+            .join(format!("{}.txt", profile()))
+    })
+}
+
+/// The profile from the config, or the username, fit for a file name.
+pub fn profile() -> String {
+    CONFIG
+        .profile
+        .as_ref()
+        .unwrap_or(&CONFIG.username)
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Loads the history of the current profile into `history`.
+pub fn load(history: &mut History, path: &Path) {
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    let loaded = if path.exists() {
+        history.load(path)
+    } else {
+        history.load(LEGACY_FILE)
+    };
+    if loaded.is_err() {
+        debug!("No previous history.");
+    }
+}
+
+/// Whether `line` must be kept out of the history: it contains the password
+/// or one of the words in `history_ignore`.
+pub fn is_secret(line: &str) -> bool {
+    let lower = line.to_lowercase();
+    (!CONFIG.password.is_empty() && line.contains(&CONFIG.password))
+        || CONFIG
+            .history_ignore
+            .iter()
+            .any(|w| lower.contains(&w.to_lowercase()))
+}
+
+/// Expands a history reference: `!!` (last command), `!n` (command number n),
+/// `!-n` (n-th last command) or `!prefix` (last command starting with prefix).
+/// Returns `Ok(None)` if `line` isn't a history reference.
+pub fn expand(line: &str, history: &History) -> Result<Option<String>, String> {
+    let reference = match line.trim().strip_prefix('!') {
+        Some(r) if !r.is_empty() => r,
+        _ => return Ok(None),
+    };
+
+    let entry = if reference == "!" {
+        history.last()
+    } else if let Ok(n) = reference.parse::<i64>() {
+        let index = if n < 0 {
+            history.len() as i64 + n
+        } else {
+            n - 1
+        };
+        if index < 0 {
+            None
+        } else {
+            history.get(index as usize)
+        }
+    } else {
+        history.iter().rev().find(|e| e.starts_with(reference))
+    };
+
+    match entry {
+        Some(e) => Ok(Some(e.clone())),
+        None => Err(format!("!{}: event not found", reference)),
+    }
+}
+
+/// Prints the history, numbered for `!n`, optionally only the entries
+/// containing a pattern and only the last `--count` ones.
+pub fn print(history: &History, args: &ArgMatches) {
+    let pattern = args.value_of("pattern").map(str::to_lowercase);
+    let mut entries: Vec<(usize, &String)> = history
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| match &pattern {
+            Some(p) => e.to_lowercase().contains(p),
+            None => true,
+        })
+        .collect();
+
+    if let Some(count) = args.value_of("count").and_then(|c| c.parse::<usize>().ok()) {
+        entries = entries.split_off(entries.len().saturating_sub(count));
+    }
+    for (i, e) in entries {
+        println!("{:>5}  {}", (i + 1).to_string().info(), e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_references() {
+        let mut history = History::new();
+        for line in ["list --default", "inspect 3", "delete 4", "inspect 5"] {
+            history.add(line);
+        }
+        let expand = |line| expand(line, &history);
+
+        assert_eq!(expand("list"), Ok(None));
+        assert_eq!(expand("!"), Ok(None));
+        assert_eq!(expand("!!"), Ok(Some("inspect 5".to_string())));
+        assert_eq!(expand(" !1 "), Ok(Some("list --default".to_string())));
+        assert_eq!(expand("!-2"), Ok(Some("delete 4".to_string())));
+        assert_eq!(expand("!ins"), Ok(Some("inspect 5".to_string())));
+        assert_eq!(expand("!li"), Ok(Some("list --default".to_string())));
+
+        for missing in ["!0", "!5", "!-5", "!create"] {
+            assert!(expand(missing).is_err(), "{}", missing);
+        }
+        assert!(super::expand("!!", &History::new()).is_err());
+    }
+
+    #[test]
+    fn keeps_secrets_out() {
+        // password "robot" in tests/config.yaml
+        assert!(is_secret("create -n robot"));
+        assert!(is_secret("set TOKEN=abc"));
+        assert!(is_secret("login --Password x"));
+        assert!(!is_secret("list --default"));
+        assert!(!is_secret("create -n Robots"));
+    }
+}
@@ -0,0 +1,288 @@
+//! Sends the requests to the GamifyUser server, logging each of them and
+//! going through the cassette when recording or replaying, or through the
+//! cache otherwise. Binary replies (images) come back base64-encoded, as
+//! the cassettes keep them.
+//!
+//! Failed GETs are sent again, up to `retries` times, waiting `retry_delay`
+//! milliseconds before the first retry and twice as long before each next,
+//! up to a minute. Once the running operation is cancelled, they aren't.
+
+use crate::{
+    cache,
+    cassette::{self, Interaction},
+    logging::redact,
+    progress, CONFIG, USER_AGENT,
+};
+use log::{info, trace, warn};
+use reqwest::{
+    blocking::{Client, Request, RequestBuilder},
+    Certificate, Method, Proxy, StatusCode,
+};
+use serde::de::DeserializeOwned;
+use std::{
+    fmt, fs, thread,
+    time::{Duration, Instant},
+};
+
+/// The longest wait between two retries, in milliseconds.
+const MAX_RETRY_DELAY: u64 = 60 * 1000;
+/// How often a wait for a retry checks for Ctrl-C.
+const CANCEL_CHECK: Duration = Duration::from_millis(100);
+
+/// A response, with its body already read.
+pub struct Reply {
+    pub status: StatusCode,
+    pub body: String,
+}
+
+impl Reply {
+    pub fn json<T: DeserializeOwned>(&self) -> serde_json::Result<T> {
+        serde_json::from_str(&self.body)
+    }
+
+    /// The body of a `download`.
+    pub fn bytes(&self) -> Result<Vec<u8>, base64::DecodeError> {
+        base64::decode(&self.body)
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Request(reqwest::Error),
+    /// Replaying, and this request isn't in the cassette.
+    NotRecorded(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Request(e) => e.fmt(f),
+            Error::NotRecorded(e) => f.write_str(e),
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Request(e)
+    }
+}
+
+/// The client all requests go through, set up as the config says: timeouts,
+/// proxy, trusted certificates.
+pub fn client() -> Result<Client, String> {
+    let mut builder = Client::builder()
+        .user_agent(&*USER_AGENT)
+        .cookie_store(true)
+        .connect_timeout(Duration::from_secs(CONFIG.connect_timeout))
+        .timeout(Duration::from_secs(CONFIG.timeout));
+
+    // without one, reqwest uses HTTP_PROXY and HTTPS_PROXY
+    if let Some(proxy) = &CONFIG.proxy {
+        let proxy = Proxy::all(proxy).map_err(|e| format!("Invalid proxy {}: {}", proxy, e))?;
+        builder = builder.proxy(proxy);
+    }
+    for path in &CONFIG.ca_certificates {
+        let bytes = fs::read(path).map_err(|e| format!("Can't read {}: {}", path, e))?;
+        let certificate = Certificate::from_pem(&bytes)
+            .or_else(|_| Certificate::from_der(&bytes))
+            .map_err(|e| format!("Invalid certificate {}: {}", path, e))?;
+        builder = builder.add_root_certificate(certificate);
+    }
+    if CONFIG.accept_invalid_certs {
+        warn!("Accepting invalid certificates, as set in the config.");
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+
+    builder
+        .build()
+        .map_err(|e| format!("Error in client creation: {}", e))
+}
+
+pub fn send(client: &Client, request: RequestBuilder) -> Result<Reply, Error> {
+    exchange(client, request, false)
+}
+
+/// Like `send`, for a binary reply, which `Reply::bytes` decodes.
+pub fn download(client: &Client, request: RequestBuilder) -> Result<Reply, Error> {
+    exchange(client, request, true)
+}
+
+fn exchange(client: &Client, request: RequestBuilder, binary: bool) -> Result<Reply, Error> {
+    let request = request.build()?;
+    let method = request.method().clone();
+    // relative to the base link, for the logs and the cassettes
+    let url = {
+        let full = request.url().as_str();
+        let relative = full
+            .strip_prefix(CONFIG.base_link.as_str())
+            .unwrap_or_else(|| request.url().path());
+        match relative.split_once('?') {
+            Some((path, q)) => format!("{}?{}", path, redact(q)),
+            None => relative.to_string(),
+        }
+    };
+    let body = request.body().map(|b| match b.as_bytes() {
+        Some(b) => redact(&String::from_utf8_lossy(b)),
+        None => "<streamed>".to_string(),
+    });
+    if let Some(b) = &body {
+        trace!("{} {} body: {}", method, url, b);
+    }
+
+    // cassettes hold every request, so the cache stays out of their way
+    let cached = method == Method::GET && !cassette::active();
+    if cached {
+        if let Some(r) = cache::get(&url) {
+            info!("{} {} -> {} (cached)", method, url, r.status.as_u16());
+            return Ok(r);
+        }
+    }
+
+    let start = Instant::now();
+    let reply = match cassette::lookup(method.as_str(), &url) {
+        Some(recorded) => recorded.map_err(Error::NotRecorded),
+        None => {
+            let retries = if method == Method::GET || method == Method::HEAD {
+                CONFIG.retries
+            } else {
+                0
+            };
+            execute(client, request, retries, &url, binary)
+        }
+    };
+    let elapsed = start.elapsed().as_millis();
+
+    match &reply {
+        Ok(r) => {
+            info!(
+                "{} {} -> {} ({} ms)",
+                method,
+                url,
+                r.status.as_u16(),
+                elapsed
+            );
+            if binary {
+                trace!(
+                    "{} {} response: {} bytes",
+                    method,
+                    url,
+                    r.body.len() / 4 * 3
+                );
+            } else {
+                trace!("{} {} response: {}", method, url, r.body);
+            }
+            if cached {
+                cache::put(&url, r);
+            } else if r.status.is_success() {
+                cache::invalidate(&url);
+            }
+            let recorded = cassette::add(Interaction {
+                method: method.to_string(),
+                url,
+                request: body,
+                status: r.status.as_u16(),
+                response: r.body.clone(),
+            });
+            if let Err(e) = recorded {
+                warn!("{}", e);
+            }
+        }
+        Err(e) => info!("{} {} -> failed after {} ms: {}", method, url, elapsed, e),
+    }
+    reply
+}
+
+/// Sends `request`, again after a while if it fails (or gets a server
+/// error) and there are `retries` left.
+fn execute(
+    client: &Client,
+    request: Request,
+    retries: u32,
+    url: &str,
+    binary: bool,
+) -> Result<Reply, Error> {
+    let method = request.method().clone();
+    let mut request = request;
+    let mut attempt = 0;
+    loop {
+        let next = if attempt < retries {
+            request.try_clone()
+        } else {
+            None
+        };
+        let result = client.execute(request).and_then(|res| {
+            let status = res.status();
+            let body = if binary {
+                res.bytes().map(base64::encode)
+            } else {
+                res.text()
+            };
+            body.map(|body| Reply { status, body })
+        });
+        let failure = match &result {
+            Ok(r) if r.status.is_server_error() => Some(r.status.to_string()),
+            Ok(_) => None,
+            Err(e) => Some(e.to_string()),
+        };
+
+        match (next, failure) {
+            (Some(next), Some(failure)) if !progress::cancelled() => {
+                let delay = retry_delay(CONFIG.retry_delay, attempt);
+                info!(
+                    "{} {} failed ({}), retrying in {} ms",
+                    method, url, failure, delay
+                );
+                let delay = Duration::from_millis(delay);
+                let asleep = Instant::now();
+                while asleep.elapsed() < delay && !progress::cancelled() {
+                    thread::sleep(CANCEL_CHECK.min(delay.saturating_sub(asleep.elapsed())));
+                }
+                if progress::cancelled() {
+                    return result.map_err(Error::from);
+                }
+                request = next;
+                attempt += 1;
+            }
+            _ => return result.map_err(Error::from),
+        }
+    }
+}
+
+/// Milliseconds to wait before retry `attempt` (from 0), doubling `first`
+/// each time.
+fn retry_delay(first: u64, attempt: u32) -> u64 {
+    first
+        .saturating_mul(2u64.saturating_pow(attempt))
+        .min(MAX_RETRY_DELAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stub;
+
+    #[test]
+    fn doubles_the_delay_up_to_a_minute() {
+        let delays: Vec<u64> = (0..4).map(|a| retry_delay(500, a)).collect();
+        assert_eq!(delays, [500, 1000, 2000, 4000]);
+        assert_eq!(retry_delay(500, 100), MAX_RETRY_DELAY);
+        assert_eq!(retry_delay(u64::MAX, 1), MAX_RETRY_DELAY);
+        assert_eq!(retry_delay(0, 10), 0);
+    }
+
+    #[test]
+    fn gives_up_retrying_once_cancelled() {
+        let client = client().unwrap();
+        let url = "admin/listQuestionnaires?start=70&size=10&past=true";
+        stub::on("GET", url, 503, "");
+
+        let reply = progress::cancellable(|| {
+            progress::cancel();
+            send(&client, client.get(&format!("{}{}", CONFIG.base_link, url)))
+        });
+        assert_eq!(reply.unwrap().status, StatusCode::SERVICE_UNAVAILABLE);
+        // retries: 1 in tests/config.yaml
+        assert_eq!(stub::hits("GET", url), 1);
+    }
+}
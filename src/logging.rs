@@ -0,0 +1,130 @@
+//! Minimal `log` backend: messages go to stderr, or to the `log_file` from
+//! the config when set.
+//!
+//! Levels by number of `-v`: none shows warnings and errors, `-v` adds the
+//! requests made (method, URL, status, latency), `-vv` debugging details and
+//! `-vvv` request and response bodies, with credentials redacted.
+
+use crate::theme::Paint;
+use colored::Colorize;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    sync::Mutex,
+};
+
+struct Logger {
+    level: LevelFilter,
+    file: Option<Mutex<File>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level && metadata.target().starts_with("gamify_rust")
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        match &self.file {
+            Some(file) => {
+                let mut file = file.lock().unwrap();
+                let _ = writeln!(
+                    file,
+                    "{} {:<5} {}",
+                    chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+                    record.level(),
+                    record.args()
+                );
+            }
+            None => {
+                let level = match record.level() {
+                    Level::Error => "error".error().bold(),
+                    Level::Warn => "warn".warning(),
+                    Level::Info => "info".success(),
+                    Level::Debug => "debug".info(),
+                    Level::Trace => "trace".stat(),
+                };
+                let _ = writeln!(io::stderr(), "{} {}", level, record.args());
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            let _ = file.lock().unwrap().flush();
+        }
+    }
+}
+
+/// Level for the given number of `-v` flags.
+pub fn level(verbosity: u64) -> LevelFilter {
+    match verbosity {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
+
+/// Installs the logger. Falls back to stderr if `file` can't be opened.
+pub fn init(level: LevelFilter, file: Option<&str>) {
+    let file =
+        file.and_then(
+            |path| match OpenOptions::new().create(true).append(true).open(path) {
+                Ok(f) => Some(Mutex::new(f)),
+                Err(e) => {
+                    eprintln!("{}", format!("Can't open log file {}: {}", path, e).error());
+                    None
+                }
+            },
+        );
+    if log::set_boxed_logger(Box::new(Logger { level, file })).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+/// Masks the values of credentials in a form or query string, e.g.
+/// `username=mr&pwd=robot` becomes `username=mr&pwd=***`.
+pub fn redact(body: &str) -> String {
+    const SECRETS: [&str; 4] = ["pwd", "password", "passwd", "token"];
+    body.split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((k, _)) if SECRETS.contains(&k.to_lowercase().as_str()) => format!("{}=***", k),
+            _ => pair.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_credentials() {
+        assert_eq!(
+            redact("id=3&token=c2VjcmV0==&start=0"),
+            "id=3&token=***&start=0"
+        );
+        assert_eq!(
+            redact("username=mr&pwd=rob%26ot&Password=x"),
+            "username=mr&pwd=***&Password=***"
+        );
+        assert_eq!(redact("pwd="), "pwd=***");
+    }
+
+    #[test]
+    fn leaves_the_rest_alone() {
+        for text in [
+            "id=3&start=0&size=100",
+            "name=Pizza&date=2021-01-10",
+            "pwdx=1&my_token=2&password",
+            "",
+        ] {
+            assert_eq!(redact(text), text);
+        }
+    }
+}
@@ -1,17 +1,55 @@
 #![allow(non_snake_case)]
-
+#![allow(clippy::upper_case_acronyms)]
+// everything hangs off main(), which the test harness doesn't call
+#![cfg_attr(test, allow(dead_code))]
+
+mod banner;
+mod browse;
+mod cache;
+mod calendar;
+mod cassette;
+mod clone;
+mod compare;
+mod date;
+mod edit;
+mod history;
+mod http;
+mod logging;
+mod mock_server;
+mod moderation;
+mod pager;
+mod participation;
+mod plan;
+mod progress;
+mod prompt;
+mod script;
+mod search;
+mod shell;
+#[cfg(test)]
+mod stub;
+mod table;
+mod theme;
+mod watch;
+
+use clap::ArgMatches;
 use colored::Colorize;
-use dialoguer::{Confirm, Input, Select};
-use figlet_rs::FIGfont;
 use lazy_static::lazy_static;
+use log::{debug, info, warn};
 use native_dialog::FileDialog;
 use reqwest::{
     blocking::{multipart, Client},
     StatusCode,
 };
 use rustyline::error::ReadlineError;
-use serde::Deserialize;
-use std::{env, fmt::Display, process::exit, time::Duration};
+use serde::{Deserialize, Serialize};
+use std::{
+    env,
+    fs::{self, File},
+    path::{Path, PathBuf},
+    process::{self, exit},
+};
+use table::{Align, Cell, Column, Table};
+use theme::{Paint, Role};
 extern crate term_size;
 
 #[non_exhaustive]
@@ -23,6 +61,8 @@ impl ENDPOINT {
     const ADMIN_LIST: &'static str = "admin/listQuestionnaires";
     const ADMIN_CREATE: &'static str = "admin/create";
     const ADMIN_DELETE: &'static str = "admin/delete";
+    /// Not in every GamifyUser version, see edit.rs.
+    const ADMIN_UPDATE: &'static str = "admin/update";
     const ADMIN_INSPECT: &'static str = "admin/listQuestionnaireCompletedUsers";
     const ADMIN_INSPECT_CANCELED: &'static str = "admin/listQuestionnaireCanceledUsers";
     const ADMIN_ANSWERS_RETRIEVAL: &'static str = "admin/getAnswers";
@@ -34,19 +74,23 @@ lazy_static! {
         env!("CARGO_PKG_VERSION"),
         env::consts::OS
     );
+    static ref MATCHES: ArgMatches = if cfg!(test) {
+        shell::cli().get_matches_from(["gamify-rust", "--config", "tests/config.yaml"])
+    } else {
+        shell::cli().get_matches()
+    };
     //static ref BASE_LINK: &'static str = "asd";
     // read config
     static ref CONFIG: Config = {
-        let f = std::fs::File::open("config.yaml").expect("Config file not found");
+        let path = MATCHES.value_of("config").unwrap_or("config.yaml");
+        let f = std::fs::File::open(path).expect("Config file not found");
         serde_yaml::from_reader(f).expect("Config not readable")
     };
     static ref TERMINAL_DIMENSIONS: (usize, usize) = {
         if let Some((w, h)) = term_size::dimensions() {
             (w,h)
         } else {
-            if CONFIG.debug {
-                println!("Unable to get term size :(")
-            }
+            debug!("Unable to get term size :(");
             (137, 35)
         }
     };
@@ -54,30 +98,32 @@ lazy_static! {
 
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 struct Questionnaire {
     questionnaireId: i32,
-    datetime: String,
+    #[serde(with = "date")]
+    datetime: chrono::NaiveDate,
     image: String,
     name: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 struct AnswerList {
     stats: Vec<Option<String>>,
     opt: Vec<OptionalAnswer>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 struct OptionalAnswer {
     question: String,
     content: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 struct User {
     userId: i32,
-    birth: String,
+    #[serde(with = "date")]
+    birth: chrono::NaiveDate,
     sex: String,
     username: String,
 }
@@ -86,615 +132,873 @@ struct User {
 struct Config {
     username: String,
     password: String,
+    /// Same as -vv.
+    #[serde(default)]
     debug: bool,
+    /// Log to this file instead of stderr.
+    #[serde(default)]
+    log_file: Option<String>,
     history: bool,
+    /// Seconds to wait for the server to answer.
+    #[serde(default = "default_timeout", alias = "read_timeout")]
+    timeout: u64,
+    /// Seconds to wait for a connection to the server.
+    #[serde(default = "default_connect_timeout")]
+    connect_timeout: u64,
+    /// Times a failed GET is sent again.
+    #[serde(default = "default_retries")]
+    retries: u32,
+    /// Milliseconds before the first retry, doubling at each next one.
+    #[serde(default = "default_retry_delay")]
+    retry_delay: u64,
+    /// Proxy for every request, instead of HTTP_PROXY and HTTPS_PROXY.
+    #[serde(default)]
+    proxy: Option<String>,
+    /// Certificates to trust besides the system ones, PEM or DER files.
+    #[serde(default)]
+    ca_certificates: Vec<String>,
+    /// Accept self-signed or otherwise invalid certificates, e.g. of a local Tomcat.
+    #[serde(default)]
+    accept_invalid_certs: bool,
+    /// Show the banners when the shell opens.
+    #[serde(default = "default_banner")]
+    banner: bool,
+    /// Built-in font (isometric3, standard) or .flf file for the greeting.
+    #[serde(default)]
+    banner_font: Option<String>,
+    /// default, light-terminal, high-contrast or monochrome.
+    #[serde(default = "default_theme")]
+    theme: String,
+    /// How dates are shown, see chrono's strftime.
+    #[serde(default = "default_date_format")]
+    date_format: String,
+    /// Locale of the month and day names, e.g. it_IT.
+    #[serde(default)]
+    locale: Option<String>,
     #[serde(default = "default_base_link")]
     #[serde(alias = "baselink")]
     base_link: String,
+    /// Name under which the history is kept, defaults to the username.
+    #[serde(default)]
+    profile: Option<String>,
+    #[serde(default)]
+    history_file: Option<String>,
+    #[serde(default = "default_history_size")]
+    history_size: usize,
+    /// Commands containing any of these words aren't saved in the history.
+    #[serde(default = "default_history_ignore")]
+    history_ignore: Vec<String>,
+    /// Questionnaire templates for `plan --fill`: a YAML file or a directory of them.
+    #[serde(default)]
+    templates: Option<String>,
+    /// Replies kept on disk, see cache.rs.
+    #[serde(default = "default_cache")]
+    cache: bool,
+    #[serde(default)]
+    cache_ttl: cache::Ttl,
+    /// Pager of long answers, see pager.rs.
+    #[serde(default)]
+    pager: Option<String>,
+    /// Answers as `Q:` / `A:` pairs rather than centered.
+    #[serde(default)]
+    compact_answers: bool,
+    /// Word list of `moderate`, see moderation.rs.
+    #[serde(default)]
+    moderation: Option<String>,
 }
 
 fn default_base_link() -> String {
     ENDPOINT::DEFAULT_BASE_LINK.to_string()
 }
 
-impl Display for User {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let date0: Vec<&str> = self.birth.split(",").collect();
-        let mut datel: Vec<&str> = Vec::with_capacity(28);
-        datel.push(date0[0]);
-        datel.push(date0[1]);
-
-        write!(
-            f,
-            "│ {: ^5} │ {: ^30} │ {: >16} │ {: ^7} │",
-            &self.userId.to_string(),
-            &self.username,
-            format! {"{:?}{:?}", datel[0], datel[1]},
-            &self.sex
-        )
-    }
+fn default_banner() -> bool {
+    true
 }
 
-fn main() {
-    if CONFIG.debug {
-        println!("Terminal dimensions: {:#?}", &*TERMINAL_DIMENSIONS);
-    }
-
-    println!(
-        "{:~^width$}\n{:^width$}\n{:^width$}\n{:^width$}",
-        " Gamify CLI ".bold().on_black().bold().bright_blue(),
-        env!("CARGO_PKG_VERSION").bright_blue(),
-        "https://github.com/darklamp/gamify-rust".magenta(),
-        "Press CTRL+C to exit or anything to get help."
-            .italic()
-            .white(),
-        width = TERMINAL_DIMENSIONS.0,
-    );
+fn default_cache() -> bool {
+    true
+}
 
-    let ascii_font = FIGfont::from_file("resources/isometric3.flf").unwrap();
+fn default_theme() -> String {
+    "default".to_string()
+}
 
-    let mut rl = rustyline::Editor::<()>::new();
+fn default_date_format() -> String {
+    date::DEFAULT_FORMAT.to_string()
+}
 
-    // try and load history file if history option is on
-    if CONFIG.history {
-        if rl.load_history(".gamify_history.txt").is_err() && CONFIG.debug {
-            println!("No previous history.");
+fn default_timeout() -> u64 {
+    10
+}
+
+fn default_connect_timeout() -> u64 {
+    5
+}
+
+fn default_retries() -> u32 {
+    3
+}
+
+fn default_retry_delay() -> u64 {
+    500
+}
+
+fn default_history_size() -> usize {
+    1000
+}
+
+fn default_history_ignore() -> Vec<String> {
+    ["password", "passwd", "pwd", "secret", "token"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect()
+}
+
+fn main() {
+    // parse the command line before anything else, so that --help works without a config
+    let command = MATCHES.subcommand();
+    theme::init(MATCHES.is_present("plain"));
+
+    // the mock server stands in for the real one, it needs no config nor login
+    if let Some(("mock-server", args)) = command {
+        logging::init(
+            logging::level(MATCHES.occurrences_of("verbose").max(1)),
+            None,
+        );
+        if !mock_server::run(args) {
+            exit(1);
         }
+        return;
     }
 
-    //let yaml = load_yaml!("../cli.yaml");
-    // let matches = App::from(yaml).get_matches();
+    let verbosity = match MATCHES.occurrences_of("verbose") {
+        v if CONFIG.debug => v.max(2),
+        v => v,
+    };
+    logging::init(logging::level(verbosity), CONFIG.log_file.as_deref());
+    if let Err(e) = theme::set(&CONFIG.theme) {
+        warn!("{}", e);
+    }
+    progress::handle_ctrl_c();
 
-    let client = reqwest::blocking::Client::builder()
-        .user_agent(&*USER_AGENT)
-        .cookie_store(true)
-        .build()
-        .expect("Error in client creation");
+    let cassette = match (MATCHES.value_of("record"), MATCHES.value_of("replay")) {
+        (Some(path), _) => cassette::record(path),
+        (_, Some(path)) => cassette::replay(path),
+        _ => Ok(()),
+    };
+    if let Err(e) = cassette {
+        println!("{}", e.error());
+        exit(1);
+    }
+
+    debug!("Terminal dimensions: {:?}", *TERMINAL_DIMENSIONS);
 
-    let mut role: &str = "admin";
-    let foo: String;
+    // commands are read from a script when asked to, or when piped in
+    let script = match MATCHES.value_of("script") {
+        Some(path) => Some(path),
+        None if command.is_none() && !atty::is(atty::Stream::Stdin) => Some("-"),
+        None => None,
+    };
+
+    // a subcommand on the command line runs just that command, without the shell
+    let interactive = script.is_none()
+        && match command {
+            Some(("admin", m)) => m.subcommand().is_none(),
+            Some(_) => false,
+            None => true,
+        };
+    prompt::set_interactive(script.is_none() && (interactive || atty::is(atty::Stream::Stdin)));
+    prompt::set_assume_yes(MATCHES.is_present("yes"));
+
+    // banners only for people at a terminal that wants them
+    let banners = interactive && CONFIG.banner && !theme::is_plain();
+    if banners {
+        banner::welcome();
+    }
 
-    match login(&client, &CONFIG.username, &CONFIG.password) {
+    let client = match http::client() {
+        Ok(c) => c,
+        Err(e) => {
+            println!("{}", e.error());
+            exit(1);
+        }
+    };
+
+    let role: String = match login(&client, &CONFIG.username, &CONFIG.password) {
         Ok(res) => {
-            foo = res.clone();
-            role = foo.strip_prefix("/GamifyUser/").unwrap();
-            if CONFIG.debug {
-                println!("{}", "Login OK".bold().green());
+            info!("Login OK as {}", CONFIG.username);
+            if banners {
+                banner::greet(&CONFIG.username, CONFIG.banner_font.as_deref());
             }
-            if (CONFIG.username.len() + 3) <= (TERMINAL_DIMENSIONS.0 as f64 / 13.6) as usize {
-                println!(
+            res.trim_start_matches("/GamifyUser/").to_string()
+        }
+        Err(e) => {
+            println!("{}", e.error().bold());
+            exit(1);
+        }
+    };
+
+    if let Some(path) = script {
+        if !script::run_file(&client, path) {
+            exit(1);
+        }
+        return;
+    }
+
+    match command {
+        Some(("admin", m)) => match m.subcommand() {
+            Some((name, args)) => {
+                if !progress::cancellable(|| admin_command(&client, name, args)) {
+                    exit(1);
+                }
+            }
+            None => admin_shell(&client),
+        },
+        Some(("user", _)) => println!("{}", "Unimplemented :(".accent()),
+        _ => {
+            if role == "admin" {
+                admin_shell(&client)
+            }
+        }
+    }
+}
+
+fn admin_shell(client: &Client) {
+    let mut rl = rustyline::Editor::<()>::with_config(
+        rustyline::Config::builder()
+            .max_history_size(CONFIG.history_size)
+            .history_ignore_dups(true)
+            .history_ignore_space(true)
+            .build(),
+    );
+
+    // try and load history file if history option is on
+    let history_path = if CONFIG.history {
+        history::path()
+    } else {
+        None
+    };
+    if let Some(path) = &history_path {
+        history::load(rl.history_mut(), path);
+    }
+
+    let prompt = format!("{}{}", CONFIG.username.info(), " >> ".info());
+    loop {
+        let readline = rl.readline(&prompt);
+        match readline {
+            Ok(line) => {
+                let line = match history::expand(&line, rl.history()) {
+                    Ok(Some(l)) => {
+                        println!("{}", l);
+                        l
+                    }
+                    Ok(None) => line,
+                    Err(e) => {
+                        println!("{}\n", e.error());
+                        continue;
+                    }
+                };
+                if CONFIG.history && !history::is_secret(&line) {
+                    rl.add_history_entry(line.as_str());
+                }
+                match shell::parse_admin(&line) {
+                    Ok(Some(matches)) => match matches.subcommand() {
+                        Some(("exit", _)) => clean_exit(),
+                        Some(("history", args)) => history::print(rl.history(), args),
+                        Some((name, args)) => {
+                            progress::cancellable(|| admin_command(client, name, args));
+                        }
+                        None => {}
+                    },
+                    Ok(None) => {}
+                    Err(e) => print!("{}", e),
+                }
+                if let Some(path) = &history_path {
+                    if let Err(e) = rl.append_history(path) {
+                        warn!("Unable to save history to {}: {}", path.display(), e);
+                    }
+                }
+                println!();
+            }
+            // Ctrl-C drops the line being typed, Ctrl-D leaves
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => clean_exit(),
+            _ => {
+                print!(
                     "{}",
-                    ascii_font
-                        .convert(format!("Hi {}", CONFIG.username).as_str())
-                        .unwrap()
-                        .to_string()
-                        .blue()
-                );
-            } else {
-                println!(
-                    "{}\n{}",
-                    ascii_font.convert("Hi").unwrap().to_string().blue(),
-                    ascii_font
-                        .convert(format!("{}", CONFIG.username).as_str())
-                        .unwrap()
-                        .to_string()
-                        .blue()
-                );
+                    "Error in command. Please enter correct command or press CTRL+D to exit"
+                        .error()
+                        .bold()
+                )
             }
         }
+    }
+}
+
+/// Runs one of the `admin` subcommands defined in cli.yaml, prompting for
+/// whatever was not given as an argument. Returns false if the command failed.
+fn admin_command(client: &Client, name: &str, args: &ArgMatches) -> bool {
+    match name {
+        "cache" => cache::command(args),
+        "calendar" => calendar::command(client, args),
+        "clone" => clone::command(client, args),
+        "compare" => compare::command(client, args),
+        "create" => create_command(client, args),
+        "list" => list_command(client, args),
+        "moderate" => moderation::command(client, args),
+        "plan" => plan::command(client, args),
+        "search" => search::command(client, args),
+        "user" => participation::command(client, args),
+        "watch" => watch::command(client, args),
+        "inspect" => inspect_command(client, args),
+        "delete" => delete_command(client, args),
+        "edit" => edit::command(client, args),
         _ => {
-            println!("{}", "Login KO".bold().red());
-            clean_exit();
+            println!("{}", "Unimplemented :(".accent());
+            false
         }
     }
+}
 
-    /*   if let Some(ref matches) = matches.subcommand_matches("admin") {
-        if matches.is_present("create") {
-            println!(
-                "{}",
-                &*USER_AGENT.italic().white()
-            );
-        } else if matches.is_present("list") {
-            println!("{}", "asd".italic().white())
-        } else {
-            println!("{}", "Unimplemented :(".bright_blue());
-            return;
+/// Value of `arg`, asked for if it wasn't given.
+fn arg_or_input(
+    args: &ArgMatches,
+    arg: &str,
+    prompt: &str,
+    default: Option<&str>,
+) -> Option<String> {
+    match args.value_of(arg) {
+        Some(v) => Some(v.to_string()),
+        None => prompt::input(prompt, default),
+    }
+}
+
+fn create_command(client: &Client, args: &ArgMatches) -> bool {
+    let name = match arg_or_input(args, "name", "Questionnaire name", None) {
+        Some(n) => n,
+        None => return false,
+    };
+    let date = match arg_or_input(args, "date", "Date (YYYY-MM-DD)", None) {
+        Some(d) => d,
+        None => return false,
+    };
+
+    let image_picker = if args.is_present("picture") || !prompt::is_interactive() {
+        Ok(None)
+    } else {
+        FileDialog::new()
+            .set_location("~/Desktop")
+            .add_filter("Image", &["png", "jpg", "jpeg", "heic"])
+            .show_open_single_file()
+    };
+
+    // checks if image picker errors out, for example on an headless machine
+    let image = match image_picker {
+        Ok(Some(path)) => {
+            println!("Loading image picker.. ");
+            path.into_os_string().into_string().unwrap()
         }
-    }*/
+        _ => match arg_or_input(
+            args,
+            "picture",
+            "Image [ex. /home/ale/Desktop/img.jpeg]",
+            None,
+        ) {
+            Some(i) => i,
+            None => return false,
+        },
+    };
 
-    let help = |st| match st {
-        "na" => println!("{}", "Available commands: admin, user.".yellow()),
-        "admin" => print!(
+    let mut questions: Vec<String> = match args.values_of("question") {
+        Some(q) => q.map(String::from).collect(),
+        None => Vec::new(),
+    };
+    if questions.is_empty() {
+        questions = match ask_questions() {
+            Some(q) => q,
+            None => return false,
+        };
+    }
+    if questions.len() > MAX_QUESTIONS {
+        println!(
             "{}",
-            "Available commands: create, list, delete, inspect, back.".yellow()
-        ),
-        _ => print!("{}", "Available commands: admin, user.".yellow()),
+            format!("At most {} questions, please.", MAX_QUESTIONS).error()
+        );
+        return false;
+    }
+
+    if create_questionnaire(client, name, date, image, questions) {
+        println!("{}", "Questionnaire submitted successfully!".success());
+        true
+    } else {
+        println!("{}", "Questionnaire submission failed!".error());
+        false
+    }
+}
+
+/// Asks for questions until told to stop, or until there are as many as a
+/// questionnaire takes.
+fn ask_questions() -> Option<Vec<String>> {
+    let mut questions = Vec::new();
+    let mut question_count: u8 = 0;
+    loop {
+        let question = prompt::input(
+            &format!(
+                "{}{}",
+                "Question #".info(),
+                question_count.to_string().info()
+            ),
+            None,
+        )?;
+        if !question.is_empty() {
+            questions.push(question);
+            if questions.len() == MAX_QUESTIONS || !prompt::confirm("Continue?", false) {
+                return Some(questions);
+            } else {
+                question_count += 1;
+            }
+        }
+    }
+}
+
+fn list_command(client: &Client, args: &ArgMatches) -> bool {
+    let mut start: Option<String> = args.value_of("start").map(String::from);
+    let mut size: Option<String> = args.value_of("size").map(String::from);
+    let mut past: Option<String> = if args.is_present("past") {
+        Some("y".to_string())
+    } else {
+        None
     };
 
-    let mut admin = || {
-        let prompt = format!("{}{}", &(CONFIG.username).blue(), " >> ".blue());
-        loop {
-            let readline = rl.readline(&*prompt);
-            match readline {
-                Ok(line) => {
-                    if CONFIG.history {
-                        rl.add_history_entry(line.as_str());
-                    }
-                    let mut toks = line.split(' ').fuse();
-                    match toks.next() {
-                        Some("b") | Some("back") | Some("exit") => {
-                            clean_exit();
-                        }
+    if args.is_present("default") {
+        start = start.or_else(|| Some("0".to_string()));
+        size = size.or_else(|| Some("100".to_string()));
+        past = past.or_else(|| Some("y".to_string()));
+    }
 
-                        Some("create") => {
-                            let name: String = Input::new()
-                                .with_prompt("Questionnaire name")
-                                .interact_text()
-                                .unwrap();
-                            let date: String = Input::new()
-                                .with_prompt("Date (YYYY-MM-DD)")
-                                .interact_text()
-                                .unwrap();
-
-                            let image_picker = FileDialog::new()
-                                .set_location("~/Desktop")
-                                .add_filter("Image", &["png", "jpg", "jpeg", "heic"])
-                                .show_open_single_file();
-
-                            let image: String;
-
-                            // checks if image picker errors out, for example on an headless machine
-
-                            if image_picker.is_err() {
-                                image = Input::new()
-                                    .with_prompt("Image [ex. /home/ale/Desktop/img.jpeg]")
-                                    .interact_text()
-                                    .unwrap();
-                            } else {
-                                println!("Loading image picker.. ");
-                                image = image_picker
-                                    .unwrap()
-                                    .unwrap()
-                                    .into_os_string()
-                                    .into_string()
-                                    .unwrap();
-                            }
-
-                            let mut questions: Vec<String> = Vec::new();
-                            let mut question: String;
-                            let mut question_count: u8 = 0;
-                            loop {
-                                question = Input::new()
-                                    .with_prompt(format!(
-                                        "{}{}",
-                                        "Question #".blue(),
-                                        question_count.to_string().blue()
-                                    ))
-                                    .interact_text()
-                                    .unwrap();
-                                if !question.is_empty() {
-                                    questions.push(question);
-                                    if !Confirm::new().with_prompt("Continue?").interact().unwrap()
-                                    {
-                                        break;
-                                    } else {
-                                        question_count += 1;
-                                    }
-                                }
-                            }
-                            if create_questionnaire(&client, name, date, image, questions) {
-                                println!(
-                                    "{}",
-                                    "Questionnaire submitted successfully!".bright_green()
-                                );
-                            } else {
-                                println!("{}", "Questionnaire submission failed!".bright_red());
-                            }
-                        }
+    let start = start
+        .or_else(|| prompt::input("Start from [default: 0]", Some("0")))
+        .unwrap_or_default();
+    let size = size
+        .or_else(|| prompt::input("Size (10,25,50,100)", Some("100")))
+        .unwrap_or_default();
+    let past = past
+        .or_else(|| prompt::input("Only past questionnaires? (y/n)", Some("n")))
+        .unwrap_or_default();
+    let p: bool = past.to_lowercase().contains(&['y', 't'][..]);
+
+    match list(client, &start, &size, p) {
+        Ok(questionnaires) => {
+            let mut table = questionnaires_table(&questionnaires);
+            if !table.configure(args) {
+                return false;
+            }
+            table.print();
+            true
+        }
+        Err(_) => {
+            println!("{}", "Error retrieving list".error());
+            false
+        }
+    }
+}
 
-                        Some("list") => {
-                            let mut start: Option<String> = None;
-                            let mut size: Option<String> = None;
-                            let mut past: Option<String> = None;
-
-                            loop {
-                                match toks.next() {
-                                    Some(a) => match a {
-                                        "d" | "default" => {
-                                            start = Some("0".to_string());
-                                            size = Some("100".to_string());
-                                            past = Some("y".to_string());
-                                            break;
-                                        },
-                                        "start" => {
-                                            if start.is_some() {
-                                                break;
-                                            }
-                                            let x = toks.next();
-                                            if x.is_none() {
-                                                break;
-                                            } else {
-                                                start = Some(x.unwrap().to_string());
-                                            }
-                                        },
-                                        "size" => {
-                                            if size.is_some() {
-                                                break;
-                                            }
-                                            let x = toks.next();
-                                            if x.is_none() {
-                                                break;
-                                            } else {
-                                                size = Some(x.unwrap().to_string());
-                                            }
-                                        },
-                                        "past" => {
-                                            if past.is_some() {
-                                                break;
-                                            }
-                                            past = Some("y".to_string());
-                                        }
-                                        _ => break,
-                                    },
-                                    None => break,
-                                }
-                            }
-
-                            if start.is_none() {
-                                start = Some(Input::new()
-                                    .with_prompt("Start from [default: 0]")
-                                    .default("0".into())
-                                    .interact_text()
-                                    .unwrap());
-                            }
-                            if size.is_none() {
-                                size = Some(Input::new()
-                                    .with_prompt("Size (10,25,50,100)")
-                                    .default("100".into())
-                                    .interact_text()
-                                    .unwrap());
-                            }
-                            if past.is_none() {
-                                past = Some(Input::new()
-                                    .with_prompt("Only past questionnaires? (y/n)")
-                                    .default("n".into())
-                                    .interact_text()
-                                    .unwrap());
-                            }
-                            let p: bool = past.unwrap().to_lowercase().contains(|c| c == 'y' || c == 't');
-
-                            if !list(&client, &start.unwrap(), &size.unwrap(), p) {
-                                println!("{}", "Error retrieving list".red());
-                            }
-                        }
+fn questionnaires_table(questionnaires: &[Questionnaire]) -> Table {
+    let mut table = Table::new(vec![
+        Column::new("ID").style(|s| s.info()),
+        Column::new("Name")
+            .align(Align::Left)
+            .wrap()
+            .style(|s| s.accent().bold()),
+        Column::new("Date").align(Align::Right),
+    ]);
+    for q in questionnaires {
+        table.add_row(vec![
+            q.questionnaireId.into(),
+            (&q.name).into(),
+            Cell::from(date::format(q.datetime)).sorted_as(q.datetime),
+        ]);
+    }
+    table
+}
 
-                        Some("inspect") => {
-                            let mut id: String = String::from("");
-                            let mut canceled: String = String::from("");
-
-                            let mut initialized: [bool; 2] = [false, false];
-                            loop {
-                                match toks.next() {
-                                    Some(a) => {
-                                        id = a.to_string();
-                                        initialized[0] = true;
-                                        initialized[1] = true;
-                                        break;
-                                    }
-                                    None => break,
-                                }
-                            }
-
-                            if !initialized[0] {
-                                id = Input::new()
-                                    .with_prompt("Questionnaire ID [default: 0]")
-                                    .default("0".into())
-                                    .interact_text()
-                                    .unwrap();
-                            }
-                            if !initialized[1] {
-                                canceled = Input::new()
-                                    .with_prompt("Canceled users?")
-                                    .default("n".into())
-                                    .interact_text()
-                                    .unwrap();
-                            }
-
-                            let p: bool = canceled.to_lowercase().contains("y");
-
-                            match inspect(&client, &id, p) {
-                                Ok(uId) => {
-                                    if uId == -1 {
-                                        let word = match p {
-                                            true => "canceled",
-                                            _ => "answered",
-                                        };
-                                        print!(
-                                            "{0} {1} {2}",
-                                            "No one".blue(),
-                                            word.blue(),
-                                            "yet!".blue()
-                                        );
-                                    } else {
-                                        showAnswers(&client, &id, uId);
-                                    }
-                                }
-                                Err(_) => {
-                                    print!("{}", "Error in retrieving data. You probably provided a non-existent id. ಠ_ಠ".bright_red());
-                                }
-                            };
-                        }
+fn users_table<'u>(users: impl IntoIterator<Item = &'u User>) -> Table {
+    let today = date::today();
+    let mut table = Table::new(vec![
+        Column::new("ID"),
+        Column::new("Name").align(Align::Left),
+        Column::new("Birth").align(Align::Right),
+        Column::new("Age"),
+        Column::new("Sex"),
+    ]);
+    for u in users {
+        table.add_row(vec![
+            u.userId.into(),
+            (&u.username).into(),
+            Cell::from(date::format(u.birth)).sorted_as(u.birth),
+            date::age(u.birth, today).into(),
+            (&u.sex).into(),
+        ]);
+    }
+    table
+}
 
-                        Some("delete") => {
-                            let mut id: Option<String> = None;
-                            match toks.next() {
-                                Some(a) => {
-                                    id = Some(a.to_string());
-                                }
-                                None => {},
-                            }
-                            if id.is_none() {
-                                id = Some(Input::new()
-                                .with_prompt("Questionnaire ID")
-                                .interact_text()
-                                .unwrap());
-                            }
-                    
-                            if id.is_none() {
-                                println!("{}", "Please insert an id.".red());
-                            }
-
-                            else if !delete(&client, &id.clone().unwrap()) {
-                                print!("{}", "Deletion failed.".bright_red());
-                            } else {
-                                print!(
-                                    "{}{}{}",
-                                    "OK! Questionnaire ".bright_green(),
-                                    &id.unwrap().to_string().bright_green(),
-                                    " deleted.".bright_green()
-                                );
-                            }
-                        }
+/// Whether answers are shown compact, with `--compact` or as the config says.
+fn compact(args: &ArgMatches) -> bool {
+    args.is_present("compact") || CONFIG.compact_answers
+}
 
-                        Some("Ctrl-C") | Some("Ctrl-D") => clean_exit(),
+fn inspect_command(client: &Client, args: &ArgMatches) -> bool {
+    let p: bool;
+    let id: String = match args.value_of("id") {
+        Some(id) => {
+            p = args.is_present("canceled");
+            id.to_string()
+        }
+        None => {
+            let id = prompt::input("Questionnaire ID [default: 0]", Some("0")).unwrap_or_default();
+            p = args.is_present("canceled")
+                || prompt::input("Canceled users?", Some("n"))
+                    .unwrap_or_default()
+                    .to_lowercase()
+                    .contains('y');
+            id
+        }
+    };
 
-                        _ => help("admin"),
-                    };
-                    if CONFIG.history {
-                        rl.save_history(".gamify_history.txt").unwrap();
-                    }
-                    print!("\n");
-                }
-                Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => {
-                    clean_exit();
-                }
-                _ => {
-                    print!(
-                        "{}",
-                        "Error in command. Please enter correct command or press CTRL+C to exit"
-                            .bold()
-                            .red()
-                    )
-                }
-            }
+    let filter = match browse::Filter::parse(args.value_of("filter").unwrap_or("")) {
+        Ok(f) => f,
+        Err(e) => {
+            println!("{}", e.error());
+            return false;
+        }
+    };
+
+    let users = match inspect(client, &id, p) {
+        Ok(users) => users,
+        Err(_) => {
+            print!(
+                "{}",
+                "Error in retrieving data. You probably provided a non-existent id. ಠ_ಠ".error()
+            );
+            return false;
         }
     };
 
-    match role {
-        "admin" => admin(),
-        _ => {}
+    if prompt::is_interactive() && args.value_of("user").is_none() {
+        return browse::users(client, &id, p, users, filter, args);
+    }
+
+    if users.is_empty() {
+        let word = if p { "canceled" } else { "answered" };
+        print!("{0} {1} {2}", "No one".info(), word.info(), "yet!".info());
+        return true;
     }
+
+    if let Some(uId) = args.value_of("user") {
+        return match users.iter().find(|u| u.userId.to_string() == uId) {
+            Some(u) => showAnswers(client, &id, u.userId, compact(args)),
+            None => {
+                print!("{}", format!("User {} not found.", uId).error());
+                false
+            }
+        };
+    }
+
+    // nobody to pick one, print them all instead
+    let mut table = users_table(users.iter().filter(|u| filter.matches(u)));
+    if !table.configure(args) {
+        return false;
+    }
+    table.print();
+    true
 }
 
-fn login(client: &Client, username: &String, password: &String) -> Result<String, ()> {
+fn delete_command(client: &Client, args: &ArgMatches) -> bool {
+    let ids: Vec<String> = match args.values_of("id") {
+        Some(ids) => ids.map(String::from).collect(),
+        None => match prompt::input("Questionnaire IDs", None) {
+            Some(ids) => ids.split_whitespace().map(String::from).collect(),
+            None => return false,
+        },
+    };
+
+    match ids.as_slice() {
+        [] => {
+            println!("{}", "Please insert an id.".error());
+            false
+        }
+        [id] if !delete(client, id) => {
+            print!("{}", "Deletion failed.".error());
+            false
+        }
+        [id] => {
+            print!(
+                "{}{}{}",
+                "OK! Questionnaire ".success(),
+                id.success(),
+                " deleted.".success()
+            );
+            true
+        }
+        _ => {
+            let summary = delete_all(client, &ids);
+            let text = summary.describe("questionnaires deleted");
+            if summary.done == summary.total {
+                print!("{}", text.success());
+            } else {
+                print!("{}", text.error());
+            }
+            summary.done == summary.total
+        }
+    }
+}
+
+/// Deletes `ids` one after the other, until done or cancelled.
+fn delete_all(client: &Client, ids: &[String]) -> progress::Summary {
+    let mut summary = progress::Summary::new(ids.len());
+    let mut bar = progress::Progress::items("Deleting", ids.len() as u64);
+    for id in ids {
+        if progress::cancelled() {
+            break;
+        }
+        if delete(client, id) {
+            summary.done += 1;
+        } else {
+            warn!("Can't delete questionnaire {}", id);
+            summary.failed += 1;
+        }
+        bar.add(1);
+    }
+    summary
+}
+
+/// Logs in, returning the home of the user (e.g. `/GamifyUser/admin`) or
+/// what went wrong.
+fn login(client: &Client, username: &str, password: &str) -> Result<String, String> {
     let params = [("username", username), ("pwd", password)];
-    let res = client
-        .post(&format!("{}{}", CONFIG.base_link, ENDPOINT::LOGIN))
-        .form(&params)
-        .timeout(Duration::from_secs(10))
-        .send();
-    if res.is_err() {
-        print!(
-            "{}{}{}",
-            "Server ".bright_red(),
-            CONFIG.base_link.bright_red(),
-            " unreachable.".bright_red()
-        );
-        exit(0);
+    let res = http::send(
+        client,
+        client
+            .post(&format!("{}{}", CONFIG.base_link, ENDPOINT::LOGIN))
+            .form(&params),
+    );
+    match res {
+        Ok(r) if r.status == StatusCode::OK => Ok(r.body),
+        Ok(_) => Err("Login KO".to_string()),
+        Err(e) => Err(format!("Server {} unreachable: {}", CONFIG.base_link, e)),
     }
-    let res1 = res.unwrap();
-    match res1.status() {
-        StatusCode::OK => return Ok(res1.text().unwrap()),
-        _ => return Err(()),
-    };
 }
 
-fn list(client: &Client, start: &String, size: &String, past: bool) -> bool {
+/// Fetches a page of questionnaires.
+fn list(client: &Client, start: &str, size: &str, past: bool) -> Result<Vec<Questionnaire>, ()> {
     let params = [
         ("start", start),
         ("size", size),
         ("past", &past.to_string()),
     ];
-    let res = client
-        .get(&format!("{}{}", CONFIG.base_link, ENDPOINT::ADMIN_LIST))
-        .query(&params)
-        .timeout(Duration::from_secs(10))
-        .send();
-    let res1 = res.unwrap();
-    match res1.status() {
-        StatusCode::OK => {
-            let result: Vec<Questionnaire> = res1.json().unwrap();
-            println!(
-                "{:^width$}",
-                "┌─ ID ──┬───────────── Name ─────────────┬────── Date ──────┐",
-                width = TERMINAL_DIMENSIONS.0
-            );
-            for r in result {
-                let date0: Vec<&str> = r.datetime.split(",").collect();
-                let datel = format!("{} {}", date0[0], date0[1]);
-
-                println!(
-                    "{:^width$}",
-                    format!(
-                        "{}{}{}",
-                        format!("│ {:^5} ", r.questionnaireId.to_string().blue()),
-                        format!("│ {:^30} ", r.name.bright_blue().bold()),
-                        format!("│ {:>16} │", format! {"{}", datel})
-                    ),
-                    width = TERMINAL_DIMENSIONS.0 + 20
-                );
-            }
-            println!(
-                "{:^width$}",
-                "└─ ID ──┴───────────── Name ─────────────┴────── Date ──────┘",
-                width = TERMINAL_DIMENSIONS.0
-            );
-            return true;
+    let res = http::send(
+        client,
+        client
+            .get(&format!("{}{}", CONFIG.base_link, ENDPOINT::ADMIN_LIST))
+            .query(&params),
+    );
+    match res {
+        Ok(r) if r.status == StatusCode::OK => r
+            .json()
+            .map_err(|e| warn!("Malformed questionnaire list: {}", e)),
+        _ => Err(()),
+    }
+}
+
+/// All the questionnaires, past and future, page by page.
+fn all_questionnaires(client: &Client) -> Result<Vec<Questionnaire>, ()> {
+    const PAGE: usize = 100;
+    let mut all = Vec::new();
+    loop {
+        let page = list(client, &all.len().to_string(), &PAGE.to_string(), false)?;
+        let last = page.len() < PAGE;
+        all.extend(page);
+        if last {
+            return Ok(all);
         }
-        _ => return false,
-    };
+    }
+}
+
+/// The questionnaire with id `id`.
+fn find_questionnaire(client: &Client, id: i32) -> Result<Option<Questionnaire>, ()> {
+    Ok(all_questionnaires(client)?
+        .into_iter()
+        .find(|q| q.questionnaireId == id))
+}
+
+/// The questionnaire with id `id`, as typed by the admin, or what went wrong.
+fn lookup_questionnaire(client: &Client, id: &str) -> Result<Questionnaire, String> {
+    let wanted: i32 = id
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid id {}.", id))?;
+    find_questionnaire(client, wanted)
+        .map_err(|()| "Can't get the questionnaires.".to_string())?
+        .ok_or_else(|| format!("No questionnaire {}.", id))
+}
+
+/// A YYYY-MM-DD date typed by the admin.
+fn parse_date(date: &str) -> Result<chrono::NaiveDate, String> {
+    chrono::NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d")
+        .map_err(|_| format!("Invalid date {}, expected YYYY-MM-DD.", date))
 }
 
-fn showAnswers(client: &Client, questionnaireId: &String, userId: i32) {
+/// The answers of user `userId` to questionnaire `questionnaireId`.
+fn answers(client: &Client, questionnaireId: &str, userId: i32) -> Result<AnswerList, ()> {
     let params = [
         ("questionnaireId", questionnaireId),
         ("userId", &userId.to_string()),
     ];
-    let res = client
-        .get(&format!(
-            "{}{}",
-            CONFIG.base_link,
-            ENDPOINT::ADMIN_ANSWERS_RETRIEVAL
-        ))
-        .query(&params)
-        .timeout(Duration::from_secs(10))
-        .send();
-
-    if res.is_err() {
-        print!("{}", "Error retrieving answers.".red());
-    } else {
-        let r: AnswerList = res.unwrap().json().unwrap();
-
-        let line = format!(
-            "{}{}{}",
-            format!(
-                "Age: {}, ",
-                r.stats
-                    .get(0)
-                    .unwrap_or(&None)
-                    .clone()
-                    .unwrap_or("N/A".to_string())
-                    .bright_purple()
-            ),
-            format!(
-                "Sex: {}, ",
-                r.stats
-                    .get(1)
-                    .unwrap_or(&None)
-                    .clone()
-                    .unwrap_or("N/A".to_string())
-                    .bright_purple()
-            ),
-            format!(
-                "Exp: {}",
-                r.stats
-                    .get(2)
-                    .unwrap_or(&None)
-                    .clone()
-                    .unwrap_or("N/A".to_string())
-                    .bright_purple()
-            )
-        );
-
-        println!(
-            "\n{:~^width$}",
-            " Statistical answers ",
-            width = TERMINAL_DIMENSIONS.0
-        );
-
-        println!("\n{:^width$}\n", line, width = TERMINAL_DIMENSIONS.0 + 27);
-
-        println!(
-            "{:~^width$}\n",
-            " Optional answers ",
-            width = TERMINAL_DIMENSIONS.0
-        );
-
-        println!("{:width$}", "".on_purple(), width = TERMINAL_DIMENSIONS.0);
-
-        for x in r.opt {
-            println!(
-                "{:^width$}",
-                x.question.bright_yellow(),
-                width = TERMINAL_DIMENSIONS.0
-            );
-            println!(
-                "{:^width$}",
-                x.content.bright_white(),
-                width = TERMINAL_DIMENSIONS.0
-            );
-            println!("{:width$}", "".on_purple(), width = TERMINAL_DIMENSIONS.0);
+    let res = http::send(
+        client,
+        client
+            .get(&format!(
+                "{}{}",
+                CONFIG.base_link,
+                ENDPOINT::ADMIN_ANSWERS_RETRIEVAL
+            ))
+            .query(&params),
+    );
+    match res {
+        Ok(r) if r.status == StatusCode::OK => {
+            r.json().map_err(|e| warn!("Malformed answers: {}", e))
         }
-
-        println!("\n{:~^width$}", "", width = TERMINAL_DIMENSIONS.0);
+        _ => Err(()),
     }
 }
 
-fn inspect(client: &Client, id: &String, canceled: bool) -> Result<i32, ()> {
-    let params = [("id", id.as_str()), ("start", "0"), ("size", "100")];
-    let endpoint = match canceled {
-        true => ENDPOINT::ADMIN_INSPECT_CANCELED,
-        _ => ENDPOINT::ADMIN_INSPECT,
+/// Prints the answers of a user, returns false if they couldn't be retrieved.
+fn showAnswers(client: &Client, questionnaireId: &str, userId: i32, compact: bool) -> bool {
+    let r = match answers(client, questionnaireId, userId) {
+        Ok(r) => r,
+        Err(()) => {
+            print!("{}", "Error retrieving answers.".error());
+            return false;
+        }
     };
+    pager::page(&answer_lines(&r, table::width(), compact));
+    true
+}
 
-    let res = client
-        .get(&format!("{}{}", CONFIG.base_link, endpoint))
-        .query(&params)
-        .timeout(Duration::from_secs(10))
-        .send();
-    if res.is_err() {
-        return Err(());
-    }
-    let res1 = res.unwrap();
-    match res1.status() {
-        StatusCode::OK => {
-            let result: Vec<User> = res1.json().unwrap();
-            if result.is_empty() {
-                return Ok(-1);
+/// The answers as lines of at most `width` columns: centered between
+/// banners, or left-aligned `Q:` / `A:` pairs when `compact`.
+fn answer_lines(r: &AnswerList, width: usize, compact: bool) -> Vec<String> {
+    let stat = |i: usize| {
+        r.stats
+            .get(i)
+            .cloned()
+            .flatten()
+            .unwrap_or_else(|| "N/A".to_string())
+            .stat()
+    };
+    let stats = format!("Age: {}, Sex: {}, Exp: {}", stat(0), stat(1), stat(2));
+
+    let mut lines = Vec::new();
+    if compact {
+        lines.push(stats);
+        for x in &r.opt {
+            let question = table::wrap(&x.question, width.saturating_sub(3));
+            let answer = table::wrap(&x.content, width.saturating_sub(3));
+            for (i, l) in question.iter().enumerate() {
+                let label = if i == 0 { "Q: " } else { "   " };
+                lines.push(format!("{}{}", label.muted(), l.question()));
             }
-            const PROMPT: &str =
-                "   ─ ID ──┬───────────── Name ─────────────┬────── Birth ─────┬── Sex ── ";
-
-            let multiselected: Vec<String> = (&result).iter().map(|u| format!("{}", u)).collect();
-            let mut selection = Select::new()
-                .with_prompt(PROMPT)
-                .items(&multiselected[..])
-                .clear(true)
-                .interact();
-
-            while selection.is_err() {
-                selection = Select::new()
-                    .with_prompt(PROMPT)
-                    .items(&multiselected[..])
-                    .interact();
+            for (i, l) in answer.iter().enumerate() {
+                let label = if i == 0 { "A: " } else { "   " };
+                lines.push(format!("{}{}", label.muted(), l.answer()));
             }
-
-            return Ok(result[selection.unwrap()].userId);
         }
-        _ => return Err(()),
+        return lines;
+    }
+
+    // padded before being painted, as colors don't count
+    let center = |text: &str, role: Role| -> Vec<String> {
+        table::wrap(text, width)
+            .iter()
+            .map(|l| {
+                table::pad(l, width, Align::Center, ' ')
+                    .paint(role)
+                    .to_string()
+            })
+            .collect()
     };
+    let separator = format!("{:width$}", "".separator(), width = width);
+    let banner = |title: &str| format!("{:~^width$}", title, width = width);
+
+    lines.push(String::new());
+    lines.push(banner(" Statistical answers "));
+    lines.push(String::new());
+    let indent = width.saturating_sub(table::display_width(&stats)) / 2;
+    lines.push(format!("{}{}", " ".repeat(indent), stats));
+    lines.push(String::new());
+    lines.push(banner(" Optional answers "));
+    lines.push(String::new());
+    lines.push(separator.clone());
+    for x in &r.opt {
+        lines.extend(center(&x.question, Role::Question));
+        lines.extend(center(&x.content, Role::Answer));
+        lines.push(separator.clone());
+    }
+    lines.push(String::new());
+    lines.push(banner(""));
+    lines
 }
 
-fn delete(client: &Client, id: &String) -> bool {
-    let res = client
-        .delete(&format!("{}{}", CONFIG.base_link, ENDPOINT::ADMIN_DELETE))
-        .query(&[("id", id.as_str())])
-        .timeout(Duration::from_secs(10))
-        .send();
-    if res.is_err() {
-        return false;
+/// Fetches the users who completed (or canceled) questionnaire `id`, page
+/// by page.
+fn inspect(client: &Client, id: &str, canceled: bool) -> Result<Vec<User>, ()> {
+    const PAGE: usize = 100;
+    let endpoint = if canceled {
+        ENDPOINT::ADMIN_INSPECT_CANCELED
+    } else {
+        ENDPOINT::ADMIN_INSPECT
+    };
+
+    let mut all = Vec::new();
+    loop {
+        let (start, size) = (all.len().to_string(), PAGE.to_string());
+        let params = [("id", id), ("start", &start), ("size", &size)];
+        let res = http::send(
+            client,
+            client
+                .get(&format!("{}{}", CONFIG.base_link, endpoint))
+                .query(&params),
+        );
+        let res1 = match res {
+            Ok(r) => r,
+            Err(_) => return Err(()),
+        };
+        let page: Vec<User> = match res1.status {
+            StatusCode::OK => res1
+                .json()
+                .map_err(|e| warn!("Malformed user list: {}", e))?,
+            _ => return Err(()),
+        };
+        let last = page.len() < PAGE;
+        all.extend(page);
+        if last {
+            return Ok(all);
+        }
+    }
+}
+
+fn delete(client: &Client, id: &str) -> bool {
+    let res = http::send(
+        client,
+        client
+            .delete(&format!("{}{}", CONFIG.base_link, ENDPOINT::ADMIN_DELETE))
+            .query(&[("id", id)]),
+    );
+    match res {
+        Ok(r) => r.status == StatusCode::OK,
+        Err(_) => false,
     }
-    return res.unwrap().status() == StatusCode::OK;
 }
 
 fn create_questionnaire(
@@ -704,16 +1008,41 @@ fn create_questionnaire(
     image: String,
     questions: Vec<String>,
 ) -> bool {
-    let mut form = multipart::Form::new()
-        .text("name", name)
-        .text("date", date)
-        .file("image", image)
-        .unwrap();
+    let part = match image_part(&image) {
+        Ok(p) => p,
+        Err(e) => {
+            println!("{}", e.error());
+            return false;
+        }
+    };
+    let form = questionnaire_form(name, date, questions).part("image", part);
+    let res = http::send(
+        client,
+        client
+            .post(&format!("{}{}", CONFIG.base_link, ENDPOINT::ADMIN_CREATE))
+            .multipart(form),
+    );
+
+    match res.map(|r| r.status) {
+        Ok(StatusCode::OK) => true,
+        /* StatusCode::UNAUTHORIZED => {
+            //TODO
+            return false;
+        }*/
+        _ => false,
+    }
+}
 
-    let mut counter: usize = 0;
+/// The most questions a questionnaire has, one form field each.
+const MAX_QUESTIONS: usize = 6;
+
+/// Name, date and questions of a questionnaire, as create expects them.
+/// There are at most `MAX_QUESTIONS` of them.
+fn questionnaire_form(name: String, date: String, questions: Vec<String>) -> multipart::Form {
+    let mut form = multipart::Form::new().text("name", name).text("date", date);
 
     //TODO Find better way of doing this
-    const QNAMES: [&str; 6] = [
+    const QNAMES: [&str; MAX_QUESTIONS] = [
         "Question0",
         "Question1",
         "Question2",
@@ -722,26 +1051,381 @@ fn create_questionnaire(
         "Question5",
     ];
 
-    for q in questions {
+    for (counter, q) in questions.into_iter().enumerate() {
         form = form.text(QNAMES[counter], q);
-        counter += 1;
     }
-    let res = client
-        .post(&format!("{}{}", CONFIG.base_link, ENDPOINT::ADMIN_CREATE))
-        .multipart(form)
-        .send();
+    form
+}
 
-    match res.unwrap().status() {
-        StatusCode::OK => return true,
-        /* StatusCode::UNAUTHORIZED => {
-            //TODO
-            return false;
-        }*/
-        _ => return false,
-    };
+/// The image file at `image`, uploaded with a progress bar.
+fn image_part(image: &str) -> Result<multipart::Part, String> {
+    let opened = File::open(image).and_then(|f| {
+        let len = f.metadata()?.len();
+        Ok((f, len))
+    });
+    let (file, len) = opened.map_err(|e| format!("Can't read {}: {}", image, e))?;
+    let path = Path::new(image);
+    let part = multipart::Part::reader_with_length(
+        progress::ProgressReader::new(file, progress::Progress::bytes("Uploading", len)),
+        len,
+    )
+    .file_name(
+        path.file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+    )
+    .mime_str(mime_guess::from_path(path).first_or_octet_stream().as_ref())
+    .expect("mime_guess gives valid types");
+    Ok(part)
+}
+
+/// Saves the campaign image `image` of a questionnaire into a temporary
+/// file, returning its path.
+fn download_image(client: &Client, image: &str) -> Result<PathBuf, String> {
+    let url = format!("{}{}{}", CONFIG.base_link, ENDPOINT::CAMPAIGN_IMAGES, image);
+    let res = match http::download(client, client.get(&url)) {
+        Ok(r) if r.status == StatusCode::OK => r.bytes().map_err(|e| e.to_string()),
+        Ok(r) => Err(r.status.to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+    .map_err(|e| format!("Can't download image {}: {}", image, e))?;
+
+    // named like the original, the server keeps the file name
+    let name = Path::new(image)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "image".to_string());
+    let path = env::temp_dir().join(format!("gamify-{}-{}", process::id(), name));
+    fs::write(&path, &res).map_err(|e| format!("Can't save {}: {}", path.display(), e))?;
+    Ok(path)
 }
 
-fn clean_exit() {
-    println!("\n{}\n", " (ᵟຶ︵ ᵟຶ) bye (ᵟຶ︵ ᵟຶ) ".bright_blue());
+fn clean_exit() -> ! {
+    println!("\n{}\n", " (ᵟຶ︵ ᵟຶ) bye (ᵟຶ︵ ᵟຶ) ".accent());
     exit(0);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const USERS: &str = r#"[{"userId":3,"birth":"Mar 3, 1990","sex":"F","username":"alice"}]"#;
+    const ANSWERS: &str =
+        r#"{"stats":["31","F",null],"opt":[{"question":"Topping?","content":"Pineapple"}]}"#;
+
+    fn user(birth: chrono::NaiveDate) -> User {
+        User {
+            userId: 3,
+            birth,
+            sex: "F".to_string(),
+            username: "alice".to_string(),
+        }
+    }
+
+    #[test]
+    fn user_tables_show_the_birth_date_and_age() {
+        let birth = chrono::NaiveDate::from_ymd(1990, 3, 3);
+        let lines = users_table(&[user(birth)]).render(200);
+        let age = date::age(birth, date::today());
+        assert_eq!(
+            lines[1],
+            format!("│  3   │ alice  │ Mar 3 1990 │ {:^5} │   F   │", age)
+        );
+    }
+
+    #[test]
+    fn answers_wrap_to_the_width() {
+        let r: AnswerList = serde_json::from_str(
+            r#"{"stats":["30","F",null],
+                "opt":[{"question":"Why?","content":"Because the crust was thin and crispy"}]}"#,
+        )
+        .unwrap();
+        let lines: Vec<String> = answer_lines(&r, 20, true)
+            .iter()
+            .map(|l| table::strip_ansi(l))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "Age: 30, Sex: F, Exp: N/A",
+                "Q: Why?",
+                "A: Because the crust",
+                "   was thin and",
+                "   crispy"
+            ]
+        );
+
+        let lines: Vec<String> = answer_lines(&r, 20, false)
+            .iter()
+            .map(|l| table::strip_ansi(l))
+            .collect();
+        let answer = lines
+            .iter()
+            .position(|l| l == "        Why?        ")
+            .unwrap();
+        assert_eq!(lines[answer + 1], " Because the crust  ");
+        assert_eq!(lines[answer + 2], "was thin and crispy ");
+    }
+
+    #[test]
+    fn questionnaire_tables_sort_by_date() {
+        let q: Vec<Questionnaire> = serde_json::from_str(
+            r#"[{"questionnaireId":1,"datetime":"Feb 1, 2021","image":"a.png","name":"Pizza"},
+                {"questionnaireId":2,"datetime":"Jan 10, 2021","image":"b.png","name":"Sushi"}]"#,
+        )
+        .unwrap();
+        let mut table = questionnaires_table(&q);
+        table.sort_by("date", false).unwrap();
+        assert_eq!(table.order(), [1, 0]);
+    }
+
+    #[test]
+    fn models_parse_from_the_server_json() {
+        let q: Vec<Questionnaire> = serde_json::from_str(
+            r#"[{"questionnaireId":1,"datetime":"Jan 10, 2021","image":"a.png","name":"Pizza"}]"#,
+        )
+        .unwrap();
+        assert_eq!((q[0].questionnaireId, q[0].name.as_str()), (1, "Pizza"));
+
+        let users: Vec<User> = serde_json::from_str(USERS).unwrap();
+        assert_eq!(users[0].birth, chrono::NaiveDate::from_ymd(1990, 3, 3));
+        // dates without a comma used to panic when shown
+        let users: Vec<User> = serde_json::from_str(
+            r#"[{"userId":3,"birth":"1990-03-03","sex":"F","username":"alice"},
+                {"userId":4,"birth":636422400000,"sex":"M","username":"bob"}]"#,
+        )
+        .unwrap();
+        assert_eq!(users[0].birth, chrono::NaiveDate::from_ymd(1990, 3, 3));
+        assert_eq!(users.len(), 2);
+        assert!(serde_json::from_str::<Vec<User>>(
+            r#"[{"userId":3,"birth":"soon","sex":"F","username":"alice"}]"#
+        )
+        .is_err());
+
+        let answers: AnswerList = serde_json::from_str(ANSWERS).unwrap();
+        assert_eq!(
+            answers.stats,
+            [Some("31".to_string()), Some("F".to_string()), None]
+        );
+        assert_eq!(answers.opt[0].content, "Pineapple");
+
+        assert!(serde_json::from_str::<Vec<User>>(r#"[{"userId":3}]"#).is_err());
+    }
+
+    #[test]
+    fn login_returns_the_role() {
+        let client = http::client().unwrap();
+        stub::on("POST", "CheckLogin", 200, "/GamifyUser/admin");
+        assert_eq!(
+            login(&client, "mr", "robot"),
+            Ok("/GamifyUser/admin".to_string())
+        );
+        stub::on("POST", "CheckLogin", 401, "Incorrect username or password");
+        assert_eq!(login(&client, "mr", "wrong"), Err("Login KO".to_string()));
+        // used to exit
+        stub::on_after("POST", "CheckLogin", 200, "", Duration::from_secs(3));
+        assert!(login(&client, "mr", "robot")
+            .unwrap_err()
+            .contains("unreachable"));
+    }
+
+    #[test]
+    fn list_fails_on_errors_and_bad_json() {
+        let client = http::client().unwrap();
+        let url =
+            |start: &str| format!("admin/listQuestionnaires?start={}&size=10&past=true", start);
+        stub::on(
+            "GET",
+            &url("0"),
+            200,
+            r#"[{"questionnaireId":1,"datetime":"Jan 10, 2021","image":"a.png","name":"Pizza"},
+                {"questionnaireId":2,"datetime":"2021-01-11","image":"b.png","name":"Sushi"}]"#,
+        );
+        stub::on("GET", &url("10"), 500, "");
+        stub::on("GET", &url("20"), 200, "<html>not json</html>");
+        stub::on_after("GET", &url("30"), 200, "[]", Duration::from_secs(3));
+
+        assert_eq!(list(&client, "0", "10", true).unwrap().len(), 2);
+        assert!(list(&client, "10", "10", true).is_err());
+        assert!(list(&client, "20", "10", true).is_err());
+        assert!(list(&client, "30", "10", true).is_err());
+        // not stubbed
+        assert!(list(&client, "40", "10", true).is_err());
+    }
+
+    #[test]
+    fn retries_only_gets() {
+        let client = http::client().unwrap();
+        let url = "admin/listQuestionnaires?start=50&size=10&past=true";
+        stub::on("GET", url, 503, "");
+        stub::on("DELETE", "admin/delete?id=304", 503, "");
+
+        assert!(list(&client, "50", "10", true).is_err());
+        // retries: 1 in tests/config.yaml
+        assert_eq!(stub::hits("GET", url), 2);
+        assert!(!delete(&client, "304"));
+        assert_eq!(stub::hits("DELETE", "admin/delete?id=304"), 1);
+
+        // client errors won't get any better
+        let url = "admin/listQuestionnaires?start=60&size=10&past=true";
+        stub::on("GET", url, 400, "");
+        assert!(list(&client, "60", "10", true).is_err());
+        assert_eq!(stub::hits("GET", url), 1);
+    }
+
+    #[test]
+    fn inspect_fails_on_errors_and_bad_json() {
+        let client = http::client().unwrap();
+        let url = |endpoint: &str, id: &str| format!("{}?id={}&start=0&size=100", endpoint, id);
+        stub::on("GET", &url(ENDPOINT::ADMIN_INSPECT, "101"), 200, USERS);
+        stub::on(
+            "GET",
+            &url(ENDPOINT::ADMIN_INSPECT_CANCELED, "101"),
+            200,
+            "[]",
+        );
+        stub::on("GET", &url(ENDPOINT::ADMIN_INSPECT, "102"), 400, "");
+        stub::on("GET", &url(ENDPOINT::ADMIN_INSPECT, "103"), 200, "{}");
+        stub::on_after(
+            "GET",
+            &url(ENDPOINT::ADMIN_INSPECT, "104"),
+            200,
+            USERS,
+            Duration::from_secs(3),
+        );
+
+        let users = inspect(&client, "101", false).unwrap();
+        assert_eq!(users[0].username, "alice");
+        assert!(inspect(&client, "101", true).unwrap().is_empty());
+        assert!(inspect(&client, "102", false).is_err());
+        assert!(inspect(&client, "103", false).is_err());
+        assert!(inspect(&client, "104", false).is_err());
+    }
+
+    #[test]
+    fn downloads_images_through_the_cassette() {
+        let client = http::client().unwrap();
+        let url = |name: &str| format!("{}{}", ENDPOINT::CAMPAIGN_IMAGES, name);
+        stub::on("GET", &url("pizza-106.png"), 200, "\u{1}PNG\n");
+        stub::on("GET", &url("gone-106.png"), 404, "");
+        let cassette = env::temp_dir().join(format!("gamify-images-{}.yaml", process::id()));
+        let cassette = cassette.to_str().unwrap();
+
+        cassette::record(cassette).unwrap();
+        let path = download_image(&client, "pizza-106.png").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"\x01PNG\n");
+        assert!(download_image(&client, "gone-106.png").is_err());
+
+        cassette::replay(cassette).unwrap();
+        fs::remove_file(cassette).unwrap();
+        let replayed = download_image(&client, "pizza-106.png").unwrap();
+        assert_eq!(fs::read(&replayed).unwrap(), b"\x01PNG\n");
+        assert_eq!(stub::hits("GET", &url("pizza-106.png")), 1);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn inspect_gets_every_page() {
+        let client = http::client().unwrap();
+        let user = |id: usize| {
+            format!(
+                r#"{{"userId":{},"birth":"Mar 3, 1990","sex":"F","username":"user{}"}}"#,
+                id, id
+            )
+        };
+        let page = |ids: std::ops::Range<usize>| {
+            format!("[{}]", ids.map(user).collect::<Vec<_>>().join(","))
+        };
+        let url = |start: usize| {
+            format!(
+                "{}?id=105&start={}&size=100",
+                ENDPOINT::ADMIN_INSPECT,
+                start
+            )
+        };
+        stub::on("GET", &url(0), 200, &page(0..100));
+        stub::on("GET", &url(100), 200, &page(100..101));
+
+        let users = inspect(&client, "105", false).unwrap();
+        assert_eq!(users.len(), 101);
+        assert_eq!(users[100].username, "user100");
+        assert_eq!(stub::hits("GET", &url(200)), 0);
+    }
+
+    #[test]
+    fn answers_fail_on_errors_and_bad_json() {
+        let client = http::client().unwrap();
+        let url = |id: &str| {
+            format!(
+                "{}?questionnaireId={}&userId=3",
+                ENDPOINT::ADMIN_ANSWERS_RETRIEVAL,
+                id
+            )
+        };
+        stub::on("GET", &url("201"), 200, ANSWERS);
+        stub::on("GET", &url("202"), 500, ANSWERS);
+        stub::on("GET", &url("203"), 200, r#"{"stats":"nope"}"#);
+        stub::on_after("GET", &url("204"), 200, ANSWERS, Duration::from_secs(3));
+
+        assert!(showAnswers(&client, "201", 3, false));
+        assert!(!showAnswers(&client, "202", 3, false));
+        assert!(!showAnswers(&client, "203", 3, false));
+        assert!(!showAnswers(&client, "204", 3, false));
+    }
+
+    #[test]
+    fn delete_fails_on_errors() {
+        let client = http::client().unwrap();
+        stub::on("DELETE", "admin/delete?id=301", 200, "");
+        stub::on("DELETE", "admin/delete?id=302", 400, "");
+        stub::on_after(
+            "DELETE",
+            "admin/delete?id=303",
+            200,
+            "",
+            Duration::from_secs(3),
+        );
+
+        assert!(delete(&client, "301"));
+        assert!(!delete(&client, "302"));
+        assert!(!delete(&client, "303"));
+    }
+
+    #[test]
+    fn bulk_deletes_carry_on_after_failures() {
+        let client = http::client().unwrap();
+        stub::on("DELETE", "admin/delete?id=305", 200, "");
+        stub::on("DELETE", "admin/delete?id=306", 400, "");
+        stub::on("DELETE", "admin/delete?id=307", 200, "");
+        let ids: Vec<String> = (305..=307).map(|id| id.to_string()).collect();
+
+        let summary = delete_all(&client, &ids);
+        assert_eq!((summary.done, summary.failed), (2, 1));
+        assert_eq!(summary.describe("deleted"), "2 of 3 deleted, 1 failed.");
+    }
+
+    #[test]
+    fn create_fails_on_errors_and_missing_images() {
+        let client = http::client().unwrap();
+        let image = env::temp_dir().join(format!("gamify-stub-{}.png", process::id()));
+        fs::write(&image, b"not really a png").unwrap();
+        let image = image.to_str().unwrap().to_string();
+        let create = |image: &str| {
+            create_questionnaire(
+                &client,
+                "Pizza".to_string(),
+                "2021-01-10".to_string(),
+                image.to_string(),
+                vec!["Topping?".to_string()],
+            )
+        };
+
+        stub::on("POST", ENDPOINT::ADMIN_CREATE, 200, "");
+        assert!(create(&image));
+        stub::on("POST", ENDPOINT::ADMIN_CREATE, 500, "");
+        assert!(!create(&image));
+        assert!(!create("/nonexistent/image.png"));
+        fs::remove_file(&image).unwrap();
+    }
+}
@@ -0,0 +1,633 @@
+//! A stand-in for the GamifyUser server, to try the CLI without one: serves
+//! the admin endpoints from memory, seeded with made-up questionnaires around
+//! today's date, the users who answered them and their answers.
+//!
+//! Nothing is persisted, every start gets the same fresh data.
+
+use crate::{date, theme::Paint, AnswerList, OptionalAnswer, Questionnaire, User, ENDPOINT};
+use chrono::NaiveDate;
+use clap::ArgMatches;
+use log::{info, warn};
+use reqwest::Url;
+use serde::Serialize;
+use std::{collections::HashMap, io::Cursor, str::FromStr};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+type Reply = Response<Cursor<Vec<u8>>>;
+
+const PREFIX: &str = "/GamifyUser/";
+
+/// Days, relative to today, of the seeded questionnaires.
+const SEED_DAYS: [i64; 10] = [-10, -8, -7, -5, -3, -2, -1, 0, 2, 5];
+const PRODUCTS: [&str; 10] = [
+    "Pizza Margherita",
+    "Sushi Box",
+    "Espresso Machine",
+    "Running Shoes",
+    "Noise Cancelling Headphones",
+    "Electric Scooter",
+    "Yoga Mat",
+    "Smart Watch",
+    "Board Game Night",
+    "Winter Jacket",
+];
+const QUESTIONS: [&str; 5] = [
+    "Did you like the product?",
+    "Would you buy it again?",
+    "What would you improve?",
+    "How did you hear about it?",
+    "Would you recommend it to a friend?",
+];
+const ANSWERS: [&str; 6] = [
+    "Yes, absolutely",
+    "Not really",
+    "The price, it's a bit high",
+    "From a friend",
+    "Maybe, if it goes on sale",
+    "It was fine",
+];
+/// Username, birth date and sex of the seeded users, whose ids start at 1.
+const USERS: [(&str, &str, &str); 8] = [
+    ("alice", "1990-03-03", "F"),
+    ("bob", "1995-04-04", "M"),
+    ("carol", "1987-11-21", "F"),
+    ("dave", "2001-06-15", "M"),
+    ("erin", "1979-01-30", "F"),
+    ("frank", "1998-09-09", "M"),
+    ("grace", "1965-12-01", "F"),
+    ("heidi", "2003-02-28", "F"),
+];
+const EXPERTISE: [Option<&str>; 4] = [Some("LOW"), Some("MEDIUM"), Some("HIGH"), None];
+/// A 1x1 PNG, the image of every seeded questionnaire.
+const PIXEL: [u8; 67] = [
+    0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x48, 0x44, 0x52,
+    0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0x1F, 0x15, 0xC4,
+    0x89, 0x00, 0x00, 0x00, 0x0A, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9C, 0x63, 0x00, 0x01, 0x00, 0x00,
+    0x05, 0x00, 0x01, 0x0D, 0x0A, 0x2D, 0xB4, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4E, 0x44, 0xAE,
+    0x42, 0x60, 0x82,
+];
+
+struct Entry {
+    id: i32,
+    date: NaiveDate,
+    name: String,
+    image: String,
+    questions: Vec<String>,
+    /// Ids of the users who completed it, and who canceled it.
+    completed: Vec<i32>,
+    canceled: Vec<i32>,
+}
+
+impl Entry {
+    fn questionnaire(&self) -> Questionnaire {
+        Questionnaire {
+            questionnaireId: self.id,
+            datetime: self.date,
+            image: self.image.clone(),
+            name: self.name.clone(),
+        }
+    }
+}
+
+pub struct State {
+    username: String,
+    password: String,
+    sessions: Vec<String>,
+    entries: Vec<Entry>,
+    images: HashMap<String, Vec<u8>>,
+    next_id: i32,
+}
+
+impl State {
+    /// Fresh data, accepting only `username` and `password` at login.
+    pub fn new(username: &str, password: &str) -> State {
+        let today = date::today();
+        let mut images = HashMap::new();
+        let mut entries = Vec::new();
+
+        for (i, offset) in SEED_DAYS.iter().enumerate() {
+            let id = i as i32 + 1;
+            let image = format!("mock{}.png", id);
+            images.insert(image.clone(), PIXEL.to_vec());
+
+            // nobody answers the future ones yet
+            let (mut completed, mut canceled) = (Vec::new(), Vec::new());
+            if *offset <= 0 {
+                for user in 1..=USERS.len() as i32 {
+                    match (id * 7 + user * 3) % 5 {
+                        0 => canceled.push(user),
+                        1..=3 => completed.push(user),
+                        _ => {}
+                    }
+                }
+            }
+            entries.push(Entry {
+                id,
+                date: today + chrono::Duration::days(*offset),
+                name: PRODUCTS[i].to_string(),
+                image,
+                questions: (0..3)
+                    .map(|k| QUESTIONS[(i + k) % QUESTIONS.len()].to_string())
+                    .collect(),
+                completed,
+                canceled,
+            });
+        }
+
+        State {
+            username: username.to_string(),
+            password: password.to_string(),
+            sessions: Vec::new(),
+            entries,
+            images,
+            next_id: SEED_DAYS.len() as i32 + 1,
+        }
+    }
+
+    fn handle(&mut self, request: &mut Request) -> Reply {
+        let url = match Url::parse(&format!("http://localhost{}", request.url())) {
+            Ok(u) => u,
+            Err(_) => return reply(400, "Bad URL"),
+        };
+        let path = match url.path().strip_prefix(PREFIX) {
+            Some(p) => p.to_string(),
+            None => return reply(404, "Not found"),
+        };
+        let query: HashMap<String, String> = url.query_pairs().into_owned().collect();
+        let method = request.method().clone();
+
+        if path == ENDPOINT::LOGIN {
+            return match method {
+                Method::Post => self.login(request),
+                _ => reply(405, "Method not allowed"),
+            };
+        }
+        if let Some(image) = path.strip_prefix(ENDPOINT::CAMPAIGN_IMAGES) {
+            return match self.images.get(image) {
+                Some(data) => Response::from_data(data.clone()),
+                None => reply(404, "Not found"),
+            };
+        }
+        if !self.logged_in(request) {
+            return reply(403, "Not logged in");
+        }
+
+        let result = match (method, path.as_str()) {
+            (Method::Get, ENDPOINT::ADMIN_LIST) => self.list(&query),
+            (Method::Post, ENDPOINT::ADMIN_CREATE) => self.create(request),
+            (Method::Delete, ENDPOINT::ADMIN_DELETE) => self.delete(&query),
+            (Method::Get, ENDPOINT::ADMIN_INSPECT) => self.users(&query, false),
+            (Method::Get, ENDPOINT::ADMIN_INSPECT_CANCELED) => self.users(&query, true),
+            (Method::Get, ENDPOINT::ADMIN_ANSWERS_RETRIEVAL) => self.answers(&query),
+            _ => Err(reply(404, "Not found")),
+        };
+        result.unwrap_or_else(|e| e)
+    }
+
+    fn login(&mut self, request: &mut Request) -> Reply {
+        let mut body = String::new();
+        if request.as_reader().read_to_string(&mut body).is_err() {
+            return reply(400, "Bad request");
+        }
+        let form = pairs(&body);
+        if form.get("username") != Some(&self.username) || form.get("pwd") != Some(&self.password) {
+            return reply(401, "Incorrect username or password");
+        }
+
+        let session = format!("mock{}", self.sessions.len() + 1);
+        let cookie = format!("JSESSIONID={}; Path={}", session, PREFIX);
+        self.sessions.push(session);
+        reply(200, format!("{}admin", PREFIX))
+            .with_header(Header::from_bytes(&b"Set-Cookie"[..], cookie.as_bytes()).unwrap())
+    }
+
+    fn logged_in(&self, request: &Request) -> bool {
+        request
+            .headers()
+            .iter()
+            .filter(|h| h.field.equiv("Cookie"))
+            .flat_map(|h| h.value.as_str().split(';'))
+            .filter_map(|c| c.trim().strip_prefix("JSESSIONID="))
+            .any(|s| self.sessions.iter().any(|x| x == s))
+    }
+
+    fn entry(&self, id: i32) -> Result<&Entry, Reply> {
+        self.entries
+            .iter()
+            .find(|e| e.id == id)
+            .ok_or_else(|| reply(400, format!("No questionnaire with id {}", id)))
+    }
+
+    fn list(&self, query: &HashMap<String, String>) -> Result<Reply, Reply> {
+        let start: usize = param(query, "start")?;
+        let size: usize = param(query, "size")?;
+        let past = query.get("past").is_some_and(|p| p == "true");
+        let today = date::today();
+
+        let mut found: Vec<&Entry> = self
+            .entries
+            .iter()
+            .filter(|e| !past || e.date < today)
+            .collect();
+        found.sort_by_key(|e| (e.date, e.id));
+        let page: Vec<Questionnaire> = found
+            .into_iter()
+            .skip(start)
+            .take(size)
+            .map(Entry::questionnaire)
+            .collect();
+        Ok(json(&page))
+    }
+
+    fn users(&self, query: &HashMap<String, String>, canceled: bool) -> Result<Reply, Reply> {
+        let entry = self.entry(param(query, "id")?)?;
+        let start: usize = param(query, "start")?;
+        let size: usize = param(query, "size")?;
+
+        let ids = if canceled {
+            &entry.canceled
+        } else {
+            &entry.completed
+        };
+        let page: Vec<User> = ids
+            .iter()
+            .skip(start)
+            .take(size)
+            .map(|&u| user(u))
+            .collect();
+        Ok(json(&page))
+    }
+
+    fn answers(&self, query: &HashMap<String, String>) -> Result<Reply, Reply> {
+        let entry = self.entry(param(query, "questionnaireId")?)?;
+        let userId: i32 = param(query, "userId")?;
+        if !entry.completed.contains(&userId) {
+            return Err(reply(
+                400,
+                format!("User {} didn't complete questionnaire {}", userId, entry.id),
+            ));
+        }
+
+        let user = user(userId);
+        let age = date::age(user.birth, entry.date);
+        let seed = (entry.id + userId) as usize;
+        let answers = AnswerList {
+            stats: vec![
+                Some(age.to_string()),
+                Some(user.sex),
+                EXPERTISE[seed % EXPERTISE.len()].map(String::from),
+            ],
+            opt: entry
+                .questions
+                .iter()
+                .enumerate()
+                .map(|(k, q)| OptionalAnswer {
+                    question: q.clone(),
+                    content: ANSWERS[(seed + k) % ANSWERS.len()].to_string(),
+                })
+                .collect(),
+        };
+        Ok(json(&answers))
+    }
+
+    fn delete(&mut self, query: &HashMap<String, String>) -> Result<Reply, Reply> {
+        let id: i32 = param(query, "id")?;
+        self.entry(id)?;
+        self.entries.retain(|e| e.id != id);
+        Ok(reply(200, "OK"))
+    }
+
+    fn create(&mut self, request: &mut Request) -> Result<Reply, Reply> {
+        let bad = |msg: &str| reply(400, msg.to_string());
+        let boundary = request
+            .headers()
+            .iter()
+            .find(|h| h.field.equiv("Content-Type"))
+            .and_then(|h| h.value.as_str().split("boundary=").nth(1))
+            .map(|b| b.trim_matches('"').to_string())
+            .ok_or_else(|| bad("Expected multipart/form-data"))?;
+        let mut body = Vec::new();
+        request
+            .as_reader()
+            .read_to_end(&mut body)
+            .map_err(|_| bad("Bad request"))?;
+        let parts = multipart(&body, &boundary);
+
+        let text = |name: &str| {
+            parts
+                .iter()
+                .find(|p| p.name == name)
+                .map(|p| String::from_utf8_lossy(&p.data).trim().to_string())
+                .filter(|t| !t.is_empty())
+        };
+        let name = text("name").ok_or_else(|| bad("Missing name"))?;
+        let date = text("date")
+            .and_then(|d| NaiveDate::parse_from_str(&d, "%Y-%m-%d").ok())
+            .ok_or_else(|| bad("Missing or invalid date"))?;
+        let image = parts
+            .iter()
+            .find(|p| p.name == "image" && p.filename.is_some() && !p.data.is_empty())
+            .ok_or_else(|| bad("Missing image"))?;
+        let questions: Vec<String> = (0..6)
+            .filter_map(|i| text(&format!("Question{}", i)))
+            .collect();
+        if questions.is_empty() {
+            return Err(bad("At least one question is needed"));
+        }
+
+        let id = self.next_id;
+        self.next_id += 1;
+        let filename = format!("{}_{}", id, image.filename.as_deref().unwrap_or_default());
+        self.images.insert(filename.clone(), image.data.clone());
+        self.entries.push(Entry {
+            id,
+            date,
+            name,
+            image: filename,
+            questions,
+            completed: Vec::new(),
+            canceled: Vec::new(),
+        });
+        Ok(reply(200, "OK"))
+    }
+}
+
+fn user(id: i32) -> User {
+    let (username, birth, sex) = USERS[id as usize - 1];
+    User {
+        userId: id,
+        birth: NaiveDate::from_str(birth).expect("seeded birth dates are valid"),
+        sex: sex.to_string(),
+        username: username.to_string(),
+    }
+}
+
+fn reply(status: u16, body: impl Into<String>) -> Reply {
+    Response::from_string(body).with_status_code(status)
+}
+
+fn json<T: Serialize>(value: &T) -> Reply {
+    match serde_json::to_string(value) {
+        Ok(body) => reply(200, body).with_header(
+            Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap(),
+        ),
+        Err(e) => reply(500, e.to_string()),
+    }
+}
+
+/// Parameters of a query string or urlencoded form.
+fn pairs(s: &str) -> HashMap<String, String> {
+    Url::parse(&format!("http://localhost/?{}", s))
+        .map(|u| u.query_pairs().into_owned().collect())
+        .unwrap_or_default()
+}
+
+/// Value of parameter `name`, a 400 if missing or not valid.
+fn param<T: FromStr>(query: &HashMap<String, String>, name: &str) -> Result<T, Reply> {
+    query
+        .get(name)
+        .and_then(|v| v.parse().ok())
+        .ok_or_else(|| reply(400, format!("Missing or invalid parameter {}", name)))
+}
+
+struct Part {
+    name: String,
+    filename: Option<String>,
+    data: Vec<u8>,
+}
+
+/// Splits a multipart/form-data body into its parts.
+fn multipart(body: &[u8], boundary: &str) -> Vec<Part> {
+    let delimiter = format!("--{}", boundary).into_bytes();
+    split(body, &delimiter)
+        .into_iter()
+        .skip(1)
+        .filter_map(|part| {
+            let part = part.strip_prefix(b"\r\n")?;
+            let (head, data) = match split(part, b"\r\n\r\n").as_slice() {
+                [head, _, ..] => (*head, &part[head.len() + 4..]),
+                _ => return None,
+            };
+            let head = String::from_utf8_lossy(head);
+            let disposition = head
+                .lines()
+                .find(|l| l.to_lowercase().starts_with("content-disposition"))?;
+            let attribute = |key: &str| {
+                disposition
+                    .split(';')
+                    .filter_map(|a| a.trim().strip_prefix(key))
+                    .filter_map(|a| a.strip_prefix('='))
+                    .map(|a| a.trim_matches('"').to_string())
+                    .next()
+            };
+            Some(Part {
+                name: attribute("name")?,
+                filename: attribute("filename"),
+                data: data.strip_suffix(b"\r\n").unwrap_or(data).to_vec(),
+            })
+        })
+        .collect()
+}
+
+fn split<'a>(data: &'a [u8], separator: &[u8]) -> Vec<&'a [u8]> {
+    let mut pieces = Vec::new();
+    let mut rest = data;
+    while let Some(i) = rest.windows(separator.len()).position(|w| w == separator) {
+        pieces.push(&rest[..i]);
+        rest = &rest[i + separator.len()..];
+    }
+    pieces.push(rest);
+    pieces
+}
+
+/// Answers requests on `server` until the process ends.
+pub fn serve(server: Server, mut state: State) {
+    for mut request in server.incoming_requests() {
+        let response = state.handle(&mut request);
+        info!(
+            "{} {} -> {}",
+            request.method(),
+            request.url(),
+            response.status_code().0
+        );
+        if let Err(e) = request.respond(response) {
+            warn!("Unable to answer: {}", e);
+        }
+    }
+}
+
+/// The `mock-server` command. Only returns if it can't listen.
+pub fn run(args: &ArgMatches) -> bool {
+    let host = args.value_of("host").unwrap_or("127.0.0.1");
+    let port = args.value_of("port").unwrap_or("8080");
+    let username = args.value_of("username").unwrap_or("mr");
+    let password = args.value_of("password").unwrap_or("robot");
+
+    let server = match Server::http(format!("{}:{}", host, port)) {
+        Ok(s) => s,
+        Err(e) => {
+            println!(
+                "{}",
+                format!("Can't listen on {}:{}: {}", host, port, e).error()
+            );
+            return false;
+        }
+    };
+    println!(
+        "{} {} {}",
+        "Mock server listening on".success(),
+        format!("http://{}{}", server.server_addr(), PREFIX).link(),
+        format!("(login as {} / {})", username, password).muted()
+    );
+    serve(server, State::new(username, password));
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::{
+        blocking::{multipart, Client},
+        StatusCode,
+    };
+    use std::thread;
+
+    fn start() -> (Client, String) {
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let base = format!("http://{}{}", server.server_addr(), PREFIX);
+        thread::spawn(move || serve(server, State::new("mr", "robot")));
+        let client = Client::builder().cookie_store(true).build().unwrap();
+        (client, base)
+    }
+
+    fn login(client: &Client, base: &str, password: &str) -> StatusCode {
+        client
+            .post(&format!("{}{}", base, ENDPOINT::LOGIN))
+            .form(&[("username", "mr"), ("pwd", password)])
+            .send()
+            .unwrap()
+            .status()
+    }
+
+    #[test]
+    fn needs_a_login() {
+        let (client, base) = start();
+        let list = || {
+            client
+                .get(&format!("{}{}", base, ENDPOINT::ADMIN_LIST))
+                .query(&[("start", "0"), ("size", "10"), ("past", "false")])
+                .send()
+                .unwrap()
+                .status()
+        };
+
+        assert_eq!(list(), StatusCode::FORBIDDEN);
+        assert_eq!(login(&client, &base, "wrong"), StatusCode::UNAUTHORIZED);
+        assert_eq!(list(), StatusCode::FORBIDDEN);
+        assert_eq!(login(&client, &base, "robot"), StatusCode::OK);
+        assert_eq!(list(), StatusCode::OK);
+    }
+
+    #[test]
+    fn serves_the_admin_endpoints() {
+        let (client, base) = start();
+        assert_eq!(login(&client, &base, "robot"), StatusCode::OK);
+        let get = |endpoint: &str, query: &[(&str, &str)]| {
+            client
+                .get(&format!("{}{}", base, endpoint))
+                .query(query)
+                .send()
+                .unwrap()
+        };
+        let list = |past: &str| -> Vec<Questionnaire> {
+            get(
+                ENDPOINT::ADMIN_LIST,
+                &[("start", "0"), ("size", "100"), ("past", past)],
+            )
+            .json()
+            .unwrap()
+        };
+
+        let all = list("false");
+        let past = list("true");
+        assert_eq!(all.len(), SEED_DAYS.len());
+        assert!(past.len() < all.len());
+
+        let id = past[0].questionnaireId.to_string();
+        let page = [("id", id.as_str()), ("start", "0"), ("size", "100")];
+        let completed: Vec<User> = get(ENDPOINT::ADMIN_INSPECT, &page).json().unwrap();
+        let canceled: Vec<User> = get(ENDPOINT::ADMIN_INSPECT_CANCELED, &page).json().unwrap();
+        assert!(!completed.is_empty());
+        assert!(completed
+            .iter()
+            .all(|u| canceled.iter().all(|c| c.userId != u.userId)));
+
+        let user = completed[0].userId.to_string();
+        let answers: AnswerList = get(
+            ENDPOINT::ADMIN_ANSWERS_RETRIEVAL,
+            &[("questionnaireId", id.as_str()), ("userId", user.as_str())],
+        )
+        .json()
+        .unwrap();
+        assert_eq!(answers.stats.len(), 3);
+        assert_eq!(answers.opt.len(), 3);
+
+        let image = get(
+            &format!("{}{}", ENDPOINT::CAMPAIGN_IMAGES, past[0].image),
+            &[],
+        );
+        assert_eq!(image.bytes().unwrap().as_ref(), &PIXEL[..]);
+
+        let form = multipart::Form::new()
+            .text("name", "Mock test")
+            .text("date", "2021-01-10")
+            .part(
+                "image",
+                multipart::Part::bytes(PIXEL.to_vec()).file_name("pixel.png"),
+            )
+            .text("Question0", "Does it work?");
+        let created = client
+            .post(&format!("{}{}", base, ENDPOINT::ADMIN_CREATE))
+            .multipart(form)
+            .send()
+            .unwrap();
+        assert_eq!(created.status(), StatusCode::OK);
+        let new = list("true")
+            .into_iter()
+            .find(|q| q.name == "Mock test")
+            .unwrap();
+        assert_eq!(new.datetime, NaiveDate::from_ymd(2021, 1, 10));
+
+        let delete = || {
+            client
+                .delete(&format!("{}{}", base, ENDPOINT::ADMIN_DELETE))
+                .query(&[("id", new.questionnaireId)])
+                .send()
+                .unwrap()
+                .status()
+        };
+        assert_eq!(delete(), StatusCode::OK);
+        assert_eq!(delete(), StatusCode::BAD_REQUEST);
+        assert_eq!(list("false").len(), SEED_DAYS.len());
+    }
+
+    #[test]
+    fn rejects_incomplete_questionnaires() {
+        let (client, base) = start();
+        assert_eq!(login(&client, &base, "robot"), StatusCode::OK);
+        let form = multipart::Form::new()
+            .text("name", "No questions")
+            .text("date", "2021-01-10")
+            .part(
+                "image",
+                multipart::Part::bytes(PIXEL.to_vec()).file_name("a.png"),
+            );
+        let status = client
+            .post(&format!("{}{}", base, ENDPOINT::ADMIN_CREATE))
+            .multipart(form)
+            .send()
+            .unwrap()
+            .status();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,419 @@
+//! The `moderate` command: the optional answers to a questionnaire that
+//! match a word list, to review them one by one before banning anybody.
+//!
+//! The word list is a text file with one entry per line: a word, matched
+//! whole and whatever its case, or a regular expression between slashes.
+//! Lines starting with `#` are comments.
+//!
+//! ```text
+//! # insults
+//! idiot
+//! /f+u+c+k/
+//! ```
+
+use crate::{
+    answers, arg_or_input, inspect, lookup_questionnaire, progress, prompt, table, theme, CONFIG,
+};
+use clap::ArgMatches;
+use colored::Colorize;
+use dialoguer::console::{Key, Term};
+use log::warn;
+use regex::{Regex, RegexBuilder};
+use reqwest::blocking::Client;
+use serde::Serialize;
+use std::{fs, ops::Range};
+use theme::Paint;
+
+/// What the word list matches, one regex per entry.
+struct Rules(Vec<Regex>);
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Verdict {
+    Unreviewed,
+    Offensive,
+    Fine,
+}
+
+#[derive(Serialize)]
+struct Flagged {
+    user: String,
+    #[serde(rename = "userId")]
+    user_id: i32,
+    question: String,
+    answer: String,
+    /// The words matched, as written in the answer.
+    matches: Vec<String>,
+    verdict: Verdict,
+    #[serde(skip)]
+    ranges: Vec<Range<usize>>,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    questionnaire: i32,
+    name: &'a str,
+    /// YYYY-MM-DD.
+    reviewed: String,
+    answers: usize,
+    flagged: &'a [Flagged],
+}
+
+/// What `flag` found.
+struct Flagging {
+    /// Answers read.
+    read: usize,
+    /// Users whose answers couldn't be read.
+    skipped: Vec<String>,
+    flagged: Vec<Flagged>,
+}
+
+pub fn command(client: &Client, args: &ArgMatches) -> bool {
+    let id = match arg_or_input(args, "id", "Questionnaire ID", None) {
+        Some(id) => id,
+        None => return false,
+    };
+    let questionnaire = match lookup_questionnaire(client, &id) {
+        Ok(q) => q,
+        Err(e) => {
+            println!("{}", e.error());
+            return false;
+        }
+    };
+    let path = match args.value_of("words").or(CONFIG.moderation.as_deref()) {
+        Some(p) => p,
+        None => {
+            println!(
+                "{}",
+                "No word list: set --words or moderation in the config.".error()
+            );
+            return false;
+        }
+    };
+    let rules = match fs::read_to_string(path)
+        .map_err(|e| format!("Can't read {}: {}", path, e))
+        .and_then(|text| Rules::parse(&text))
+    {
+        Ok(r) => r,
+        Err(e) => {
+            println!("{}", e.error());
+            return false;
+        }
+    };
+
+    let id = questionnaire.questionnaireId.to_string();
+    let Flagging {
+        read,
+        skipped,
+        mut flagged,
+    } = match flag(client, &id, &rules) {
+        Ok(f) => f,
+        Err(()) => {
+            println!("{}", "Can't get the users who answered.".error());
+            return false;
+        }
+    };
+    println!(
+        "{} of the {} answers to {} match the word list.",
+        flagged.len().to_string().info(),
+        read.to_string().info(),
+        questionnaire.name.accent().bold()
+    );
+    if !skipped.is_empty() {
+        println!(
+            "{}",
+            format!(
+                "Can't read the answers of {}, left out.",
+                skipped.join(", ")
+            )
+            .warning()
+        );
+    }
+    if flagged.is_empty() {
+        return !progress::cancelled() && skipped.is_empty();
+    }
+
+    if prompt::is_interactive() && atty::is(atty::Stream::Stdout) {
+        review(&mut flagged);
+    } else {
+        for f in &flagged {
+            println!("\n{}", describe(f).join("\n"));
+        }
+    }
+    println!("{}", tally(&flagged));
+
+    let report = match args.value_of("report") {
+        Some(r) => Some(r.to_string()),
+        None if prompt::confirm("Save a report?", false) => prompt::input(
+            "Report file",
+            Some(&format!(
+                "moderation-{}.yaml",
+                questionnaire.questionnaireId
+            )),
+        ),
+        None => None,
+    };
+    if let Some(file) = report {
+        let report = Report {
+            questionnaire: questionnaire.questionnaireId,
+            name: &questionnaire.name,
+            reviewed: crate::date::today().to_string(),
+            answers: read,
+            flagged: &flagged,
+        };
+        let saved = serde_yaml::to_string(&report)
+            .map_err(|e| e.to_string())
+            .and_then(|yaml| fs::write(&file, yaml).map_err(|e| e.to_string()));
+        match saved {
+            Ok(()) => println!("{}", format!("Report saved to {}.", file).success()),
+            Err(e) => {
+                println!("{}", format!("Can't save {}: {}", file, e).error());
+                return false;
+            }
+        }
+    }
+    skipped.is_empty()
+}
+
+impl Rules {
+    fn parse(text: &str) -> Result<Rules, String> {
+        let mut rules = Vec::new();
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let pattern = match line.strip_prefix('/').and_then(|l| l.strip_suffix('/')) {
+                Some(re) => re.to_string(),
+                None => format!(r"\b{}\b", regex::escape(line)),
+            };
+            let rule = RegexBuilder::new(&pattern)
+                .case_insensitive(true)
+                .build()
+                .map_err(|e| format!("Invalid pattern {}: {}", line, e))?;
+            rules.push(rule);
+        }
+        if rules.is_empty() {
+            return Err("The word list is empty.".to_string());
+        }
+        Ok(Rules(rules))
+    }
+
+    /// Where `text` matches, sorted, overlapping matches merged.
+    fn find(&self, text: &str) -> Vec<Range<usize>> {
+        let mut found: Vec<Range<usize>> = self
+            .0
+            .iter()
+            .flat_map(|r| r.find_iter(text).map(|m| m.range()))
+            .filter(|r| !r.is_empty())
+            .collect();
+        found.sort_by_key(|r| r.start);
+        let mut merged: Vec<Range<usize>> = Vec::new();
+        for r in found {
+            match merged.last_mut() {
+                Some(last) if r.start <= last.end => last.end = last.end.max(r.end),
+                _ => merged.push(r),
+            }
+        }
+        merged
+    }
+}
+
+/// Reads every answer to questionnaire `id`, keeping the ones matching
+/// `rules`. Users whose answers can't be read are skipped.
+fn flag(client: &Client, id: &str, rules: &Rules) -> Result<Flagging, ()> {
+    let users = inspect(client, id, false)?;
+    let mut read = 0;
+    let mut skipped = Vec::new();
+    let mut flagged = Vec::new();
+    let mut bar = progress::Progress::items("Reading", users.len() as u64);
+    for user in users {
+        if progress::cancelled() {
+            break;
+        }
+        bar.add(1);
+        let list = match answers(client, id, user.userId) {
+            Ok(l) => l,
+            Err(()) => {
+                warn!("Can't get the answers of {} to {}", user.username, id);
+                skipped.push(user.username);
+                continue;
+            }
+        };
+        for answer in list.opt {
+            read += 1;
+            let ranges = rules.find(&answer.content);
+            if ranges.is_empty() {
+                continue;
+            }
+            let matches = ranges
+                .iter()
+                .map(|r| answer.content[r.clone()].to_string())
+                .collect();
+            flagged.push(Flagged {
+                user: user.username.clone(),
+                user_id: user.userId,
+                question: answer.question,
+                answer: answer.content,
+                matches,
+                verdict: Verdict::Unreviewed,
+                ranges,
+            });
+        }
+    }
+    Ok(Flagging {
+        read,
+        skipped,
+        flagged,
+    })
+}
+
+/// `text` with the `ranges` standing out, between brackets on plain output.
+pub fn highlight(text: &str, ranges: &[Range<usize>]) -> String {
+    let mut out = String::new();
+    let mut at = 0;
+    for r in ranges {
+        out.push_str(&text[at..r.start].answer().to_string());
+        let word = &text[r.clone()];
+        if theme::is_plain() {
+            out.push_str(&format!("[{}]", word));
+        } else {
+            out.push_str(&word.error().bold().underline().to_string());
+        }
+        at = r.end;
+    }
+    out.push_str(&text[at..].answer().to_string());
+    out
+}
+
+fn describe(f: &Flagged) -> Vec<String> {
+    let verdict = match f.verdict {
+        Verdict::Unreviewed => "unreviewed".muted(),
+        Verdict::Offensive => "offensive".error(),
+        Verdict::Fine => "fine".success(),
+    };
+    vec![
+        format!(
+            "{} #{}  {}",
+            f.user.accent().bold(),
+            f.user_id.to_string().info(),
+            verdict
+        ),
+        format!("  {}", f.question.question()),
+        format!("  {}", highlight(&f.answer, &f.ranges)),
+    ]
+}
+
+/// Steps through the flagged answers, one at a time, until the admin is done.
+fn review(flagged: &mut [Flagged]) {
+    let term = Term::stdout();
+    let keys = "→/n next  ←/p previous  o offensive  f fine  u unreviewed  q done";
+    let mut at = 0;
+    loop {
+        let mut lines = vec![format!("[{}/{}]", at + 1, flagged.len()).info().to_string()];
+        lines.extend(describe(&flagged[at]));
+        lines.push(keys.muted().to_string());
+        println!("\n{}", lines.join("\n"));
+
+        let key = match term.read_key() {
+            Ok(k) => k,
+            Err(_) => break,
+        };
+        if progress::cancelled() {
+            break;
+        }
+        let last = flagged.len() - 1;
+        match key {
+            Key::ArrowRight | Key::Char('n') | Key::Char(' ') | Key::Enter => {
+                at = (at + 1).min(last)
+            }
+            Key::ArrowLeft | Key::Char('p') => at = at.saturating_sub(1),
+            Key::Char('o') => {
+                flagged[at].verdict = Verdict::Offensive;
+                at = (at + 1).min(last);
+            }
+            Key::Char('f') => {
+                flagged[at].verdict = Verdict::Fine;
+                at = (at + 1).min(last);
+            }
+            Key::Char('u') => flagged[at].verdict = Verdict::Unreviewed,
+            Key::Char('q') | Key::Escape => break,
+            _ => {}
+        }
+        // the blank line too
+        let _ = term.clear_last_lines(rows(&lines, table::width()) + 1);
+    }
+}
+
+/// Rows `lines` take on a terminal `width` columns wide, long ones wrapping.
+fn rows(lines: &[String], width: usize) -> usize {
+    lines
+        .iter()
+        .flat_map(|l| l.split('\n'))
+        .map(|l| table::display_width(l).div_ceil(width.max(1)).max(1))
+        .sum()
+}
+
+/// e.g. `2 offensive, 1 fine, 4 unreviewed.`
+fn tally(flagged: &[Flagged]) -> String {
+    let count = |v: Verdict| flagged.iter().filter(|f| f.verdict == v).count();
+    format!(
+        "{} offensive, {} fine, {} unreviewed.",
+        count(Verdict::Offensive).to_string().error(),
+        count(Verdict::Fine).to_string().success(),
+        count(Verdict::Unreviewed).to_string().muted()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        http,
+        stub::{self, ANNA, BOB},
+    };
+
+    #[test]
+    fn matches_words_and_patterns() {
+        let rules = Rules::parse("# insults\nidiot\n\n/f+u+c+k/\n").unwrap();
+        let text = "What an IDIOT, fuuuck, idiotic";
+        let found: Vec<&str> = rules.find(text).into_iter().map(|r| &text[r]).collect();
+        assert_eq!(found, ["IDIOT", "fuuuck"]);
+        assert!(rules.find("All good").is_empty());
+
+        assert!(Rules::parse("# nothing\n").is_err());
+        assert!(Rules::parse("/(unclosed/").is_err());
+
+        let lines = [
+            "12345".to_string(),
+            "\x1b[1m1234567\x1b[0m".to_string(),
+            "a\n\nb".to_string(),
+        ];
+        assert_eq!(rows(&lines, 5), 1 + 2 + 3);
+    }
+
+    #[test]
+    fn flags_matching_answers() {
+        let client = http::client().unwrap();
+        // carol's answers aren't there
+        let carol = r#"{"userId":3,"birth":"2001-02-03","sex":"F","username":"carol"}"#;
+        stub::users(801, false, &[ANNA, carol, BOB]);
+        stub::answers(801, 1, &[("Topping?", "Basil")]);
+        stub::answers(
+            801,
+            2,
+            &[("Topping?", "Ham, you idiot"), ("Crust?", "Thin")],
+        );
+        let rules = Rules::parse("idiot").unwrap();
+        let Flagging {
+            read,
+            skipped,
+            flagged,
+        } = flag(&client, "801", &rules).unwrap();
+        assert_eq!(read, 3);
+        assert_eq!(skipped, ["carol"]);
+        assert_eq!(flagged.len(), 1);
+        assert_eq!(flagged[0].user, "bob");
+        assert_eq!(flagged[0].matches, ["idiot"]);
+        assert_eq!(flagged[0].ranges, vec![Range { start: 9, end: 14 }]);
+    }
+}
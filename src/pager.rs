@@ -0,0 +1,210 @@
+//! Output longer than the terminal goes through a pager: `pager` from the
+//! config, `$PAGER`, or the built-in one, which scrolls and searches.
+//!
+//! `pager: builtin` always uses the built-in one, `pager: off` never pages.
+
+use crate::{
+    progress, table,
+    theme::{self, Paint},
+    CONFIG,
+};
+use colored::Colorize;
+use dialoguer::console::{Key, Term};
+use log::warn;
+use std::{
+    env,
+    io::{self, Write},
+    process::{Command, Stdio},
+};
+
+const KEYS: &str = "↑↓ line  space/b page  / search  n/N next/previous  q quit";
+
+/// Prints `lines`, through a pager if they don't fit the terminal.
+pub fn page(lines: &[String]) {
+    let term = Term::stdout();
+    let height = match term.size_checked() {
+        Some((rows, _)) if term.features().is_attended() => rows as usize,
+        _ => 0,
+    };
+    let setting = CONFIG.pager.as_deref();
+    if height < 3 || lines.len() < height || setting == Some("off") {
+        for line in lines {
+            println!("{}", line);
+        }
+        return;
+    }
+
+    let external = match setting {
+        Some("builtin") => None,
+        Some(command) => Some(command.to_string()),
+        None => env::var("PAGER").ok().filter(|p| !p.trim().is_empty()),
+    };
+    if let Some(command) = external {
+        match external_pager(&command, lines) {
+            Ok(()) => return,
+            Err(e) => warn!("Can't run pager {}: {}", command, e),
+        }
+    }
+    if let Err(e) = builtin(&term, lines, height - 1) {
+        warn!("Pager failed: {}", e);
+    }
+}
+
+fn external_pager(command: &str, lines: &[String]) -> io::Result<()> {
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    let mut pager = Command::new(shell);
+    pager.arg(flag).arg(command).stdin(Stdio::piped());
+    // as git does: colors, and no pager for a single screen
+    if env::var_os("LESS").is_none() {
+        pager.env("LESS", "FRX");
+    }
+    let mut child = pager.spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // the pager may quit before reading everything
+        let _ = stdin.write_all((lines.join("\n") + "\n").as_bytes());
+    }
+    child.wait()?;
+    Ok(())
+}
+
+/// Shows `lines` a screen of `height` lines at a time, until `q`.
+fn builtin(term: &Term, lines: &[String], height: usize) -> io::Result<()> {
+    let last = lines.len().saturating_sub(height);
+    let mut top = 0;
+    let mut search: Option<String> = None;
+    let mut message = KEYS.to_string();
+    term.hide_cursor()?;
+    loop {
+        term.clear_screen()?;
+        for line in &lines[top..(top + height).min(lines.len())] {
+            match &search {
+                Some(s) => term.write_line(&highlight(line, s, theme::is_plain()))?,
+                None => term.write_line(line)?,
+            }
+        }
+        let position = format!(
+            "{}-{}/{}",
+            top + 1,
+            (top + height).min(lines.len()),
+            lines.len()
+        );
+        // a line too long would scroll the screen
+        let status = table::truncate(
+            &format!("{}  {}", position, message),
+            (term.size().1 as usize).saturating_sub(1).max(1),
+        );
+        term.write_str(&status.muted().to_string())?;
+
+        let key = term.read_key()?;
+        if progress::cancelled() {
+            break;
+        }
+        message = KEYS.to_string();
+        match key {
+            Key::ArrowDown | Key::Enter | Key::Char('j') => top = (top + 1).min(last),
+            Key::ArrowUp | Key::Char('k') => top = top.saturating_sub(1),
+            Key::Char(' ') | Key::Char('f') => top = (top + height).min(last),
+            Key::Char('b') => top = top.saturating_sub(height),
+            Key::Home | Key::Char('g') => top = 0,
+            Key::End | Key::Char('G') => top = last,
+            Key::Char('/') => {
+                term.clear_line()?;
+                term.write_str("/")?;
+                term.show_cursor()?;
+                let text = term.read_line()?;
+                term.hide_cursor()?;
+                search = Some(text).filter(|t| !t.is_empty());
+                if let Some(s) = &search {
+                    match find(lines, s, top, true) {
+                        Some(i) => top = i.min(last),
+                        None => message = format!("{} not found", s),
+                    }
+                }
+            }
+            Key::Char(c @ 'n') | Key::Char(c @ 'N') => {
+                if let Some(s) = &search {
+                    match find(lines, s, top, c == 'n') {
+                        Some(i) => top = i.min(last),
+                        None => message = format!("{} not found", s),
+                    }
+                }
+            }
+            Key::Char('q') | Key::Escape => break,
+            _ => {}
+        }
+    }
+    term.clear_line()?;
+    term.show_cursor()
+}
+
+/// The next (or previous) line after (before) `from` containing `text`,
+/// whatever the case, going round at the end.
+fn find(lines: &[String], text: &str, from: usize, forward: bool) -> Option<usize> {
+    let text = text.to_lowercase();
+    let n = lines.len();
+    (1..=n)
+        .map(|i| {
+            if forward {
+                (from + i) % n
+            } else {
+                (from + n * 2 - i) % n
+            }
+        })
+        .find(|&i| table::strip_ansi(&lines[i]).to_lowercase().contains(&text))
+}
+
+/// `line` with `text` standing out, without its other colors then, or in
+/// brackets if the output is `plain`.
+fn highlight(line: &str, text: &str, plain: bool) -> String {
+    let bare = table::strip_ansi(line);
+    let lower = bare.to_lowercase();
+    let needle = text.to_lowercase();
+    // lowercasing may change lengths, then it's not worth it
+    if needle.is_empty() || lower.len() != bare.len() || !lower.contains(&needle) {
+        return line.to_string();
+    }
+    let mut out = String::new();
+    let mut at = 0;
+    for (start, _) in lower.match_indices(&needle) {
+        out.push_str(&bare[at..start]);
+        let found = &bare[start..start + needle.len()];
+        if plain {
+            out.push_str(&format!("[{}]", found));
+        } else {
+            out.push_str(&found.reversed().to_string());
+        }
+        at = start + needle.len();
+    }
+    out.push_str(&bare[at..]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_text_both_ways() {
+        let lines: Vec<String> = ["Pizza", "pasta", "Salad", "PIZZA again"]
+            .iter()
+            .map(|l| l.to_string())
+            .collect();
+        assert_eq!(find(&lines, "pizza", 0, true), Some(3));
+        assert_eq!(find(&lines, "pizza", 3, true), Some(0));
+        assert_eq!(find(&lines, "pizza", 3, false), Some(0));
+        assert_eq!(find(&lines, "pizza", 0, false), Some(3));
+        assert_eq!(find(&lines, "sushi", 0, true), None);
+
+        colored::control::set_override(true);
+        let marked = highlight("a Pizza", "pizza", false);
+        colored::control::unset_override();
+        assert_ne!(marked, "a Pizza");
+        assert_eq!(table::strip_ansi(&marked), "a Pizza");
+        assert_eq!(highlight("a Pizza", "pizza", true), "a [Pizza]");
+        assert_eq!(highlight("a Pizza", "sushi", true), "a Pizza");
+    }
+}
@@ -0,0 +1,250 @@
+//! The admin `user` command: what one user did, questionnaire after
+//! questionnaire. The server only tells who answered a questionnaire, so
+//! every questionnaire already open is looked at.
+
+use crate::{
+    all_questionnaires, answers, date, inspect, progress, prompt,
+    table::{Align, Cell, Column, Table},
+    theme::Paint,
+    AnswerList, Questionnaire, User,
+};
+use clap::ArgMatches;
+use colored::Colorize;
+use log::warn;
+use reqwest::blocking::Client;
+
+/// A questionnaire the user completed or canceled.
+struct Participation<'q> {
+    questionnaire: &'q Questionnaire,
+    completed: bool,
+    /// Of the completed ones.
+    answers: Option<AnswerList>,
+}
+
+pub fn command(client: &Client, args: &ArgMatches) -> bool {
+    let wanted = match args
+        .value_of("user")
+        .map(String::from)
+        .or_else(|| prompt::input("Username or ID", None))
+    {
+        Some(u) => u,
+        None => return false,
+    };
+    let questionnaires = match all_questionnaires(client) {
+        Ok(q) => q,
+        Err(()) => {
+            println!("{}", "Can't get the questionnaires.".error());
+            return false;
+        }
+    };
+    let today = date::today();
+    let mut open: Vec<&Questionnaire> = questionnaires
+        .iter()
+        .filter(|q| q.datetime <= today)
+        .collect();
+    open.sort_by_key(|q| q.datetime);
+
+    let (user, timeline) = scan(client, &open, &wanted);
+    let user = match user {
+        Some(u) => u,
+        None => {
+            println!(
+                "{}",
+                format!(
+                    "{} didn't answer any of {} questionnaires.",
+                    wanted,
+                    open.len()
+                )
+                .error()
+            );
+            return false;
+        }
+    };
+
+    println!(
+        "{} #{}, {} born {} ({} years old)",
+        user.username.accent().bold(),
+        user.userId.to_string().info(),
+        user.sex,
+        date::format(user.birth),
+        date::age(user.birth, today)
+    );
+    timeline_table(&timeline).print();
+    println!("{}", summary(&timeline, open.len()));
+    if args.is_present("answers") {
+        print_answers(&timeline);
+    }
+    if progress::cancelled() {
+        println!(
+            "{}",
+            "Cancelled: not every questionnaire was looked at.".warning()
+        );
+    }
+    true
+}
+
+/// Looks for the user with username or id `wanted` among the users of each
+/// questionnaire, fetching the answers they gave.
+fn scan<'q>(
+    client: &Client,
+    questionnaires: &[&'q Questionnaire],
+    wanted: &str,
+) -> (Option<User>, Vec<Participation<'q>>) {
+    let mut user: Option<User> = None;
+    let mut timeline = Vec::new();
+    let mut bar = progress::Progress::items("Scanning", questionnaires.len() as u64);
+    for q in questionnaires {
+        if progress::cancelled() {
+            break;
+        }
+        let id = q.questionnaireId.to_string();
+        for completed in [true, false] {
+            let users = match inspect(client, &id, !completed) {
+                Ok(u) => u,
+                Err(()) => {
+                    warn!("Can't get the users of questionnaire {}", id);
+                    continue;
+                }
+            };
+            let found = users
+                .into_iter()
+                .find(|u| u.userId.to_string() == wanted || u.username == wanted);
+            if let Some(u) = found {
+                let answers = if completed {
+                    answers(client, &id, u.userId).ok()
+                } else {
+                    None
+                };
+                timeline.push(Participation {
+                    questionnaire: q,
+                    completed,
+                    answers,
+                });
+                user.get_or_insert(u);
+            }
+        }
+        bar.add(1);
+    }
+    (user, timeline)
+}
+
+fn timeline_table(timeline: &[Participation]) -> Table {
+    let mut table = Table::new(vec![
+        Column::new("Date").align(Align::Right),
+        Column::new("Questionnaire")
+            .align(Align::Left)
+            .wrap()
+            .style(|s| s.accent()),
+        Column::new("Status"),
+        Column::new("Answers"),
+    ]);
+    for p in timeline {
+        let q = p.questionnaire;
+        let status = if p.completed {
+            "completed".success()
+        } else {
+            "canceled".error()
+        };
+        let answers = match &p.answers {
+            Some(a) => a.opt.len().to_string(),
+            None if p.completed => "?".to_string(),
+            None => "-".to_string(),
+        };
+        table.add_row(vec![
+            Cell::from(date::format(q.datetime)).sorted_as(q.datetime),
+            format!("#{} {}", q.questionnaireId, q.name).into(),
+            status.into(),
+            answers.into(),
+        ]);
+    }
+    table
+}
+
+/// e.g. `Completed 5 and canceled 2 (29%) of 8 questionnaires.`
+fn summary(timeline: &[Participation], scanned: usize) -> String {
+    let completed = timeline.iter().filter(|p| p.completed).count();
+    let canceled = timeline.len() - completed;
+    format!(
+        "Completed {} and canceled {} ({:.0}%) of {} questionnaires.",
+        completed,
+        canceled,
+        canceled as f64 * 100.0 / timeline.len().max(1) as f64,
+        scanned
+    )
+}
+
+fn print_answers(timeline: &[Participation]) {
+    for p in timeline {
+        let list = match &p.answers {
+            Some(a) => a,
+            None => continue,
+        };
+        println!(
+            "\n{} {}",
+            date::format(p.questionnaire.datetime).info(),
+            p.questionnaire.name.accent().bold()
+        );
+        for a in &list.opt {
+            println!("  {} {}", a.question.question(), a.content.answer());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        http,
+        stub::{self, ANNA},
+    };
+
+    fn questionnaire(id: i32, day: u32) -> Questionnaire {
+        Questionnaire {
+            questionnaireId: id,
+            datetime: chrono::NaiveDate::from_ymd(2021, 1, day),
+            image: String::new(),
+            name: format!("Q{}", id),
+        }
+    }
+
+    #[test]
+    fn follows_a_user_across_questionnaires() {
+        let client = http::client().unwrap();
+        stub::users(701, false, &[ANNA]);
+        stub::users(701, true, &[]);
+        stub::users(702, false, &[]);
+        stub::users(702, true, &[ANNA]);
+        stub::users(703, false, &[]);
+        stub::users(703, true, &[]);
+        stub::answers(701, 1, &[("Topping?", "Basil")]);
+
+        let questionnaires = [
+            questionnaire(701, 10),
+            questionnaire(702, 11),
+            questionnaire(703, 12),
+        ];
+        let open: Vec<&Questionnaire> = questionnaires.iter().collect();
+        let (user, timeline) = scan(&client, &open, "anna");
+        assert_eq!(user.map(|u| u.userId), Some(1));
+        let steps: Vec<(i32, bool, Option<usize>)> = timeline
+            .iter()
+            .map(|p| {
+                (
+                    p.questionnaire.questionnaireId,
+                    p.completed,
+                    p.answers.as_ref().map(|a| a.opt.len()),
+                )
+            })
+            .collect();
+        assert_eq!(steps, vec![(701, true, Some(1)), (702, false, None)]);
+        assert_eq!(
+            summary(&timeline, 3),
+            "Completed 1 and canceled 1 (50%) of 3 questionnaires."
+        );
+
+        let (user, timeline) = scan(&client, &open, "1");
+        assert_eq!(user.map(|u| u.username), Some("anna".to_string()));
+        assert_eq!(timeline.len(), 2);
+        assert!(scan(&client, &open, "bob").0.is_none());
+    }
+}
@@ -0,0 +1,338 @@
+//! The `plan` command: checks that each of the days to come has its
+//! questionnaire of the day, and fills the free ones from templates.
+//!
+//! Templates are YAML files, each holding one template or a list of them:
+//!
+//! ```yaml
+//! name: Pizza of the week
+//! image: pizza.png # relative to the YAML file
+//! questions:
+//!   - Which topping?
+//! ```
+
+use crate::{
+    all_questionnaires, create_questionnaire, date, parse_date, progress, prompt,
+    table::{Align, Cell, Column, Table},
+    theme::Paint,
+    Questionnaire, CONFIG, MAX_QUESTIONS,
+};
+use chrono::{Duration, NaiveDate};
+use clap::ArgMatches;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+struct Template {
+    name: String,
+    image: PathBuf,
+    questions: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Templates {
+    One(Template),
+    Many(Vec<Template>),
+}
+
+pub fn command(client: &Client, args: &ArgMatches) -> bool {
+    let days = match args.value_of("days").unwrap_or("30").parse() {
+        Ok(d) if (1..=366).contains(&d) => d,
+        _ => {
+            println!("{}", "--days must be a number from 1 to 366.".error());
+            return false;
+        }
+    };
+    let from = match args.value_of("from").map(parse_date) {
+        Some(Ok(d)) => d,
+        Some(Err(e)) => {
+            println!("{}", e.error());
+            return false;
+        }
+        None => date::today(),
+    };
+    let templates = if args.is_present("fill") {
+        let path = args.value_of("templates").or(CONFIG.templates.as_deref());
+        match path.map(|p| load(Path::new(p))) {
+            Some(Ok(t)) => t,
+            Some(Err(e)) => {
+                println!("{}", e.error());
+                return false;
+            }
+            None => {
+                println!(
+                    "{}",
+                    "No templates to fill with: set --templates or templates in the config."
+                        .error()
+                );
+                return false;
+            }
+        }
+    } else {
+        Vec::new()
+    };
+
+    let questionnaires = match all_questionnaires(client) {
+        Ok(q) => q,
+        Err(()) => {
+            println!("{}", "Can't get the questionnaires.".error());
+            return false;
+        }
+    };
+    let schedule = schedule(&questionnaires, from, days);
+    let gaps: Vec<NaiveDate> = schedule
+        .iter()
+        .filter(|(_, q)| q.is_empty())
+        .map(|(d, _)| *d)
+        .collect();
+    let fills = assign(&gaps, &templates);
+
+    let last = from + Duration::days(days as i64 - 1);
+    if schedule.iter().all(|(_, q)| q.len() == 1) {
+        println!(
+            "{}",
+            format!(
+                "Every day from {} to {} has its questionnaire.",
+                date::format(from),
+                date::format(last)
+            )
+            .success()
+        );
+        return true;
+    }
+    table(&schedule, &fills).print();
+    let duplicates = schedule.iter().filter(|(_, q)| q.len() > 1).count();
+    println!(
+        "{} free days and {} days with several questionnaires, from {} to {}.",
+        gaps.len().to_string().warning(),
+        duplicates.to_string().error(),
+        date::format(from),
+        date::format(last)
+    );
+
+    if fills.is_empty() {
+        return true;
+    }
+    if !prompt::confirm(&format!("Create {} questionnaires?", fills.len()), true) {
+        return true;
+    }
+    let mut summary = progress::Summary::new(fills.len());
+    let mut bar = progress::Progress::items("Filling", fills.len() as u64);
+    for (day, template) in &fills {
+        if progress::cancelled() {
+            break;
+        }
+        if create_questionnaire(
+            client,
+            template.name.clone(),
+            day.to_string(),
+            template.image.to_string_lossy().into_owned(),
+            template.questions.clone(),
+        ) {
+            summary.done += 1;
+        } else {
+            println!("{}", format!("Can't create the one of {}.", day).error());
+            summary.failed += 1;
+        }
+        bar.add(1);
+    }
+    drop(bar);
+    let text = summary.describe("questionnaires created");
+    if summary.done == summary.total {
+        print!("{}", text.success());
+        true
+    } else {
+        print!("{}", text.error());
+        false
+    }
+}
+
+/// The questionnaires of each of the `days` days from `from`.
+fn schedule(
+    questionnaires: &[Questionnaire],
+    from: NaiveDate,
+    days: usize,
+) -> Vec<(NaiveDate, Vec<&Questionnaire>)> {
+    from.iter_days()
+        .take(days)
+        .map(|day| {
+            let on_day = questionnaires.iter().filter(|q| q.datetime == day);
+            (day, on_day.collect())
+        })
+        .collect()
+}
+
+/// A template for each free day, taking turns.
+fn assign<'t>(gaps: &[NaiveDate], templates: &'t [Template]) -> Vec<(NaiveDate, &'t Template)> {
+    gaps.iter()
+        .zip(templates.iter().cycle())
+        .map(|(d, t)| (*d, t))
+        .collect()
+}
+
+/// The days needing attention: free ones, with the template that would
+/// fill them, and the ones with several questionnaires.
+fn table(schedule: &[(NaiveDate, Vec<&Questionnaire>)], fills: &[(NaiveDate, &Template)]) -> Table {
+    let mut table = Table::new(vec![
+        Column::new("Date").align(Align::Right),
+        Column::new("Questionnaires").align(Align::Left).wrap(),
+        Column::new("Plan").align(Align::Left),
+    ]);
+    for (day, questionnaires) in schedule.iter().filter(|(_, q)| q.len() != 1) {
+        let names: Vec<String> = questionnaires
+            .iter()
+            .map(|q| format!("#{} {}", q.questionnaireId, q.name))
+            .collect();
+        let plan = match fills.iter().find(|(d, _)| d == day) {
+            Some((_, t)) => format!("create {}", t.name).success(),
+            None if questionnaires.is_empty() => "free".warning(),
+            None => "several".error(),
+        };
+        table.add_row(vec![
+            Cell::from(date::format(*day)).sorted_as(day),
+            names.join(", ").into(),
+            plan.into(),
+        ]);
+    }
+    table
+}
+
+/// The templates at `path`, a YAML file or a directory of them.
+fn load(path: &Path) -> Result<Vec<Template>, String> {
+    let files = if path.is_dir() {
+        let mut files: Vec<PathBuf> = fs::read_dir(path)
+            .map_err(|e| format!("Can't read {}: {}", path.display(), e))?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|e| e == "yaml" || e == "yml"))
+            .collect();
+        files.sort();
+        files
+    } else {
+        vec![path.to_path_buf()]
+    };
+
+    let mut templates = Vec::new();
+    for file in files {
+        let content = fs::read_to_string(&file)
+            .map_err(|e| format!("Can't read {}: {}", file.display(), e))?;
+        let found = match serde_yaml::from_str(&content) {
+            Ok(Templates::One(t)) => vec![t],
+            Ok(Templates::Many(t)) => t,
+            Err(e) => return Err(format!("Invalid template {}: {}", file.display(), e)),
+        };
+        let dir = file.parent().unwrap_or_else(|| Path::new(""));
+        for mut t in found {
+            t.image = dir.join(&t.image);
+            if !t.image.is_file() {
+                return Err(format!(
+                    "Template {} in {}: no image {}.",
+                    t.name,
+                    file.display(),
+                    t.image.display()
+                ));
+            }
+            if t.questions.is_empty() || t.questions.len() > MAX_QUESTIONS {
+                return Err(format!(
+                    "Template {} in {}: give 1 to {} questions.",
+                    t.name,
+                    file.display(),
+                    MAX_QUESTIONS
+                ));
+            }
+            templates.push(t);
+        }
+    }
+    if templates.is_empty() {
+        return Err(format!("No templates in {}.", path.display()));
+    }
+    Ok(templates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn questionnaire(id: i32, day: &str) -> Questionnaire {
+        Questionnaire {
+            questionnaireId: id,
+            datetime: date(day),
+            image: String::new(),
+            name: format!("Q{}", id),
+        }
+    }
+
+    #[test]
+    fn finds_free_and_crowded_days() {
+        let questionnaires = vec![
+            questionnaire(1, "2021-01-09"),
+            questionnaire(2, "2021-01-10"),
+            questionnaire(3, "2021-01-12"),
+            questionnaire(4, "2021-01-12"),
+        ];
+        let schedule = schedule(&questionnaires, date("2021-01-10"), 4);
+        let ids: Vec<Vec<i32>> = schedule
+            .iter()
+            .map(|(_, q)| q.iter().map(|q| q.questionnaireId).collect())
+            .collect();
+        assert_eq!(ids, vec![vec![2], vec![], vec![3, 4], vec![]]);
+        assert_eq!(schedule[3].0, date("2021-01-13"));
+    }
+
+    #[test]
+    fn takes_turns_filling() {
+        let template = |name: &str| Template {
+            name: name.to_string(),
+            image: PathBuf::new(),
+            questions: vec!["?".to_string()],
+        };
+        let templates = vec![template("a"), template("b")];
+        let gaps = [date("2021-01-11"), date("2021-01-13"), date("2021-01-14")];
+        let names: Vec<&str> = assign(&gaps, &templates)
+            .iter()
+            .map(|(_, t)| t.name.as_str())
+            .collect();
+        assert_eq!(names, ["a", "b", "a"]);
+        assert!(assign(&gaps, &[]).is_empty());
+    }
+
+    #[test]
+    fn loads_templates_from_a_directory() {
+        let dir = env::temp_dir().join(format!("gamify-templates-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("pizza.png"), b"png").unwrap();
+        fs::write(
+            dir.join("a.yaml"),
+            "name: Pizza\nimage: pizza.png\nquestions: [Topping?]\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("b.yml"),
+            "- name: Pasta\n  image: pizza.png\n  questions: [Sauce?, Shape?]\n\
+             - name: Salad\n  image: pizza.png\n  questions: [Dressing?]\n",
+        )
+        .unwrap();
+        fs::write(dir.join("notes.txt"), "not a template").unwrap();
+
+        let templates = load(&dir).unwrap();
+        let names: Vec<&str> = templates.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["Pizza", "Pasta", "Salad"]);
+        assert_eq!(templates[0].image, dir.join("pizza.png"));
+
+        fs::write(
+            dir.join("c.yaml"),
+            "name: Cake\nimage: cake.png\nquestions: [Icing?]\n",
+        )
+        .unwrap();
+        assert!(load(&dir).unwrap_err().contains("no image"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
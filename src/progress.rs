@@ -0,0 +1,277 @@
+//! Progress of long operations (uploads, commands working on many items) and
+//! their cancellation with Ctrl-C.
+//!
+//! While an operation runs, Ctrl-C only raises a flag: the operation checks
+//! it between steps, stops, and reports what it got done. Outside of one,
+//! Ctrl-C exits as usual.
+
+use crate::{table, theme};
+use std::{
+    io::{self, Read, Write},
+    process::exit,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+#[cfg(not(test))]
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+#[cfg(test)]
+thread_local! {
+    // per thread, so that a test cancelling its operation doesn't cancel
+    // the ones of the tests running along
+    static CANCELLED: AtomicBool = const { AtomicBool::new(false) };
+}
+/// Whether a bar is on screen; the bars of steps of a bigger operation
+/// (the upload of each copy of a series) don't show over its own.
+static SHOWING: AtomicBool = AtomicBool::new(false);
+
+/// Routes Ctrl-C to the running operation, if any.
+pub fn handle_ctrl_c() {
+    let handler = ctrlc::set_handler(|| {
+        if RUNNING.load(Ordering::SeqCst) {
+            cancel();
+        } else {
+            exit(130);
+        }
+    });
+    if let Err(e) = handler {
+        log::warn!("Ctrl-C will exit instead of cancelling: {}", e);
+    }
+}
+
+/// Runs `operation`, which Ctrl-C now cancels rather than exiting.
+pub fn cancellable<T>(operation: impl FnOnce() -> T) -> T {
+    set_cancelled(false);
+    RUNNING.store(true, Ordering::SeqCst);
+    let result = operation();
+    RUNNING.store(false, Ordering::SeqCst);
+    result
+}
+
+/// Cancels the running operation, as Ctrl-C does.
+pub fn cancel() {
+    set_cancelled(true);
+}
+
+/// Whether Ctrl-C was pressed during the current operation.
+pub fn cancelled() -> bool {
+    #[cfg(not(test))]
+    return CANCELLED.load(Ordering::SeqCst);
+    #[cfg(test)]
+    return CANCELLED.with(|c| c.load(Ordering::SeqCst));
+}
+
+fn set_cancelled(cancelled: bool) {
+    #[cfg(not(test))]
+    CANCELLED.store(cancelled, Ordering::SeqCst);
+    #[cfg(test)]
+    CANCELLED.with(|c| c.store(cancelled, Ordering::SeqCst));
+}
+
+#[derive(Clone, Copy)]
+enum Unit {
+    Bytes,
+    Items,
+}
+
+/// A progress bar on stderr, drawn only if that's a terminal and no other
+/// bar is.
+pub struct Progress {
+    label: String,
+    unit: Unit,
+    done: u64,
+    total: u64,
+    drawn: Option<Instant>,
+    visible: bool,
+}
+
+impl Progress {
+    pub fn bytes(label: &str, total: u64) -> Progress {
+        Progress::new(label, total, Unit::Bytes)
+    }
+
+    pub fn items(label: &str, total: u64) -> Progress {
+        Progress::new(label, total, Unit::Items)
+    }
+
+    fn new(label: &str, total: u64, unit: Unit) -> Progress {
+        Progress {
+            label: label.to_string(),
+            unit,
+            done: 0,
+            total,
+            drawn: None,
+            visible: atty::is(atty::Stream::Stderr)
+                && !theme::is_plain()
+                && !SHOWING.swap(true, Ordering::SeqCst),
+        }
+    }
+
+    pub fn add(&mut self, n: u64) {
+        self.done = (self.done + n).min(self.total);
+        // redrawing at every chunk of an upload would only slow it down
+        let due = self
+            .drawn
+            .is_none_or(|t| t.elapsed() >= Duration::from_millis(100));
+        if due || self.done == self.total {
+            self.draw();
+        }
+    }
+
+    fn count(&self, n: u64) -> String {
+        match self.unit {
+            Unit::Bytes => human_bytes(n),
+            Unit::Items => n.to_string(),
+        }
+    }
+
+    /// `label [#####     ] done/total`, fitting `width`.
+    fn line(&self, width: usize) -> String {
+        let counts = format!("{}/{}", self.count(self.done), self.count(self.total));
+        let room = width.saturating_sub(table::display_width(&self.label) + counts.len() + 5);
+        let bar = room.min(40);
+        let filled = (bar as u64 * self.done)
+            .checked_div(self.total)
+            .map_or(bar, |f| f as usize);
+        format!(
+            "{} [{}{}] {}",
+            self.label,
+            "#".repeat(filled),
+            " ".repeat(bar - filled),
+            counts
+        )
+    }
+
+    fn draw(&mut self) {
+        if !self.visible {
+            return;
+        }
+        self.drawn = Some(Instant::now());
+        let mut err = io::stderr();
+        let _ = write!(err, "\r\x1b[K{}", self.line(table::width()));
+        let _ = err.flush();
+    }
+}
+
+impl Drop for Progress {
+    // leaves the bar as it got, on its own line
+    fn drop(&mut self) {
+        if self.visible && self.drawn.is_some() {
+            self.draw();
+            eprintln!();
+        }
+        if self.visible {
+            SHOWING.store(false, Ordering::SeqCst);
+        }
+    }
+}
+
+/// A reader reporting what's read from it, that fails once cancelled.
+pub struct ProgressReader<R> {
+    inner: R,
+    progress: Progress,
+}
+
+impl<R: Read> ProgressReader<R> {
+    pub fn new(inner: R, progress: Progress) -> ProgressReader<R> {
+        ProgressReader { inner, progress }
+    }
+}
+
+impl<R: Read> Read for ProgressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if cancelled() {
+            return Err(io::Error::other("cancelled"));
+        }
+        let n = self.inner.read(buf)?;
+        self.progress.add(n as u64);
+        Ok(n)
+    }
+}
+
+/// What a command working on many items got done.
+#[derive(Default)]
+pub struct Summary {
+    pub done: usize,
+    pub failed: usize,
+    pub total: usize,
+}
+
+impl Summary {
+    pub fn new(total: usize) -> Summary {
+        Summary {
+            total,
+            ..Summary::default()
+        }
+    }
+
+    /// e.g. `2 of 5 deleted, 1 failed, cancelled.`
+    pub fn describe(&self, verb: &str) -> String {
+        let mut s = format!("{} of {} {}", self.done, self.total, verb);
+        if self.failed > 0 {
+            s += &format!(", {} failed", self.failed);
+        }
+        if self.done + self.failed < self.total {
+            s += ", cancelled";
+        }
+        s + "."
+    }
+}
+
+pub fn human_bytes(n: u64) -> String {
+    match n {
+        n if n >= 1 << 20 => format!("{:.1} MiB", n as f64 / (1 << 20) as f64),
+        n if n >= 1 << 10 => format!("{:.1} KiB", n as f64 / (1 << 10) as f64),
+        n => format!("{} B", n),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draws_a_bar_fitting_the_width() {
+        let mut p = Progress::bytes("Uploading", 4096);
+        p.add(1024);
+        assert_eq!(
+            p.line(80),
+            format!(
+                "Uploading [{}{}] 1.0 KiB/4.0 KiB",
+                "#".repeat(10),
+                " ".repeat(30)
+            )
+        );
+        assert!(p.line(30).len() <= 30);
+        p.add(10000);
+        assert!(p.line(80).ends_with("] 4.0 KiB/4.0 KiB"));
+
+        let mut p = Progress::items("Deleting", 3);
+        p.add(1);
+        assert!(p.line(80).ends_with("] 1/3"));
+    }
+
+    #[test]
+    fn summarizes() {
+        let mut s = Summary::new(3);
+        s.done = 3;
+        assert_eq!(s.describe("deleted"), "3 of 3 deleted.");
+        s.done = 1;
+        s.failed = 1;
+        assert_eq!(
+            s.describe("deleted"),
+            "1 of 3 deleted, 1 failed, cancelled."
+        );
+    }
+
+    #[test]
+    fn readers_count_what_they_read() {
+        let data = vec![7u8; 3000];
+        let mut reader = ProgressReader::new(&data[..], Progress::bytes("Reading", 3000));
+        let mut out = Vec::new();
+        reader.read_to_end(&mut out).unwrap();
+        assert_eq!(out, data);
+        assert_eq!(reader.progress.done, 3000);
+    }
+}
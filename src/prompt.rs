@@ -0,0 +1,109 @@
+//! Wrappers around the dialoguer prompts that fall back to defaults when
+//! nobody is there to answer them (scripts, pipes).
+
+use crate::{progress, theme::Paint};
+use dialoguer::{Confirm, Input, Select};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERACTIVE: AtomicBool = AtomicBool::new(true);
+/// `--yes`: what to answer the confirmations when not interactive.
+static ASSUME_YES: AtomicBool = AtomicBool::new(false);
+
+pub fn set_interactive(interactive: bool) {
+    INTERACTIVE.store(interactive, Ordering::Relaxed);
+}
+
+pub fn is_interactive() -> bool {
+    INTERACTIVE.load(Ordering::Relaxed)
+}
+
+pub fn set_assume_yes(yes: bool) {
+    ASSUME_YES.store(yes, Ordering::Relaxed);
+}
+
+/// Asks for a line of text. When not interactive the default is used; without
+/// one the missing value is reported and `None` returned.
+pub fn input(prompt: &str, default: Option<&str>) -> Option<String> {
+    if !is_interactive() {
+        if default.is_none() {
+            println!("{}{}", prompt.error(), ": no value given.".error());
+        }
+        return default.map(String::from);
+    }
+    let mut input = Input::<String>::new();
+    input.with_prompt(prompt);
+    if let Some(d) = default {
+        input.default(d.to_string());
+    }
+    input.interact_text().ok()
+}
+
+/// Asks a yes/no question. When not interactive the answer is no, unless
+/// `--yes` was given and `default` is yes.
+pub fn confirm(prompt: &str, default: bool) -> bool {
+    if !is_interactive() {
+        let yes = ASSUME_YES.load(Ordering::Relaxed);
+        if default && !yes {
+            println!(
+                "{}{}",
+                prompt.warning(),
+                " No: pass --yes to confirm.".warning()
+            );
+        }
+        return default && yes;
+    }
+    Confirm::new()
+        .with_prompt(prompt)
+        .default(default)
+        .interact()
+        .unwrap_or(default)
+}
+
+/// Lets the user pick one of `items`. Always `None` when not interactive.
+pub fn select<T: ToString>(prompt: &str, items: &[T]) -> Option<usize> {
+    select_at(prompt, items, 0)
+}
+
+/// Like `select`, starting on item `default`.
+pub fn select_at<T: ToString>(prompt: &str, items: &[T], default: usize) -> Option<usize> {
+    if !is_interactive() {
+        return None;
+    }
+    let select = || {
+        Select::new()
+            .with_prompt(prompt)
+            .items(items)
+            .default(default)
+            .clear(true)
+            .interact()
+    };
+    let mut selection = select();
+
+    // a cancelled command stops asking
+    while selection.is_err() && !progress::cancelled() {
+        selection = select();
+    }
+    selection.ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn falls_back_without_a_terminal() {
+        let interactive = is_interactive();
+        set_interactive(false);
+        assert_eq!(input("Name", Some("Pizza")), Some("Pizza".to_string()));
+        assert_eq!(input("Name", None), None);
+        assert_eq!(select("Which one?", &["a", "b"]), None);
+
+        assert!(!confirm("Create 3 questionnaires?", true));
+        assert!(!confirm("Save a report?", false));
+        set_assume_yes(true);
+        assert!(confirm("Create 3 questionnaires?", true));
+        assert!(!confirm("Save a report?", false));
+        set_assume_yes(false);
+        set_interactive(interactive);
+    }
+}
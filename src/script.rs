@@ -0,0 +1,199 @@
+//! Runs files of shell commands, one per line.
+//!
+//! Besides the admin shell commands, scripts understand:
+//! - `# comments`, on their own line or after a command
+//! - `set NAME=value`, then `$NAME` or `${NAME}` anywhere outside single
+//!   quotes (environment variables work as well)
+//! - `set -e` / `set +e` to stop at / carry on after the first failing command
+
+use crate::{admin_command, progress, shell, theme::Paint, CONFIG};
+use clap::ErrorKind;
+use reqwest::blocking::Client;
+use std::{
+    collections::HashMap,
+    env,
+    fs::File,
+    io::{self, BufRead, BufReader},
+};
+
+enum Status {
+    Ok,
+    Failed,
+    Exit,
+    Cancelled,
+}
+
+struct Script {
+    vars: HashMap<String, String>,
+    stop_on_error: bool,
+}
+
+/// Runs the script at `path`, or standard input if `path` is `-`.
+/// Returns false if any command failed.
+pub fn run_file(client: &Client, path: &str) -> bool {
+    if path == "-" {
+        return run(client, "stdin", BufReader::new(io::stdin()));
+    }
+    match File::open(path) {
+        Ok(f) => run(client, path, BufReader::new(f)),
+        Err(e) => {
+            println!("{}", format!("Can't open script {}: {}", path, e).error());
+            false
+        }
+    }
+}
+
+pub fn run<R: BufRead>(client: &Client, name: &str, reader: R) -> bool {
+    let mut script = Script {
+        vars: HashMap::new(),
+        stop_on_error: false,
+    };
+    let mut ok = true;
+
+    for (n, line) in reader.lines().enumerate() {
+        let line = match line {
+            Ok(l) => l,
+            Err(e) => {
+                println!("{}", format!("{}: read error: {}", name, e).error());
+                return false;
+            }
+        };
+        let failed = match script.line(client, &line) {
+            Ok(Status::Ok) => false,
+            Ok(Status::Exit) => break,
+            Ok(Status::Failed) => true,
+            Ok(Status::Cancelled) => {
+                println!(
+                    "{}",
+                    format!("{}:{}: cancelled, stopping.", name, n + 1).error()
+                );
+                return false;
+            }
+            Err(e) => {
+                println!("{}", format!("{}:{}: {}", name, n + 1, e).error());
+                true
+            }
+        };
+        ok &= !failed;
+        if failed && script.stop_on_error {
+            println!(
+                "{}",
+                format!("{}:{}: stopping after failed command.", name, n + 1).error()
+            );
+            return false;
+        }
+    }
+    ok
+}
+
+impl Script {
+    fn line(&mut self, client: &Client, line: &str) -> Result<Status, String> {
+        let vars = &self.vars;
+        let toks = shell::split_expanding(line, &|name| {
+            vars.get(name).cloned().or_else(|| env::var(name).ok())
+        })?;
+        if toks.is_empty() {
+            return Ok(Status::Ok);
+        }
+
+        println!("{}{}{}", CONFIG.username.info(), " >> ".info(), line.trim());
+        if toks[0] == "set" {
+            return self.set(&toks[1..]);
+        }
+
+        let status = match shell::parse_admin_words(&toks) {
+            Ok(Some(matches)) => match matches.subcommand() {
+                Some(("exit", _)) => Status::Exit,
+                Some((name, args)) => {
+                    match progress::cancellable(|| admin_command(client, name, args)) {
+                        _ if progress::cancelled() => Status::Cancelled,
+                        true => Status::Ok,
+                        false => Status::Failed,
+                    }
+                }
+                None => Status::Ok,
+            },
+            Ok(None) => Status::Ok,
+            Err(e) if e.kind == ErrorKind::DisplayHelp => {
+                print!("{}", e);
+                Status::Ok
+            }
+            Err(e) => {
+                print!("{}", e);
+                Status::Failed
+            }
+        };
+        println!();
+        Ok(status)
+    }
+
+    fn set(&mut self, args: &[String]) -> Result<Status, String> {
+        if args.is_empty() {
+            let mut vars: Vec<_> = self.vars.iter().collect();
+            vars.sort();
+            for (k, v) in vars {
+                println!("{}={}", k, v);
+            }
+        }
+        for arg in args {
+            match arg.as_str() {
+                "-e" => self.stop_on_error = true,
+                "+e" => self.stop_on_error = false,
+                _ => match arg.split_once('=') {
+                    Some((k, v)) if !k.is_empty() => {
+                        self.vars.insert(k.to_string(), v.to_string());
+                    }
+                    _ => return Err(format!("set: expected NAME=value, got {}", arg)),
+                },
+            }
+        }
+        Ok(Status::Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{http, stub};
+
+    fn delete(id: u32, status: u16) -> String {
+        let url = format!("admin/delete?id={}", id);
+        stub::on("DELETE", &url, status, "");
+        url
+    }
+
+    #[test]
+    fn sets_and_expands_variables() {
+        let client = http::client().unwrap();
+        let (ok, failing) = (delete(1101, 200), delete(1102, 500));
+        let script = "# cleaning up\n\
+                      set OK=1101 FAILING=1102\n\
+                      \n\
+                      delete $OK # the first\n\
+                      delete ${FAILING}\n\
+                      delete $MISSING\n\
+                      set oops\n";
+        assert!(!run(&client, "test", script.as_bytes()));
+        assert_eq!(stub::hits("DELETE", &ok), 1);
+        assert_eq!(stub::hits("DELETE", &failing), 1);
+        assert!(run(&client, "test", "# only\n  # comments\n".as_bytes()));
+    }
+
+    #[test]
+    fn stops_on_errors_when_set() {
+        let client = http::client().unwrap();
+        let urls = [delete(1103, 500), delete(1104, 200), delete(1105, 200)];
+        let script = "set +e\ndelete 1103\nset -e\ndelete 1104\ndelete 1103\ndelete 1105\n";
+        assert!(!run(&client, "test", script.as_bytes()));
+        let hits: Vec<usize> = urls.iter().map(|u| stub::hits("DELETE", u)).collect();
+        assert_eq!(hits, [2, 1, 0]);
+
+        let url = delete(1106, 200);
+        assert!(run(
+            &client,
+            "test",
+            "delete 1106\nexit\ndelete 1106\n".as_bytes()
+        ));
+        assert_eq!(stub::hits("DELETE", &url), 1);
+    }
+}
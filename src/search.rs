@@ -0,0 +1,310 @@
+//! The `search` command: which users wrote something in their answers,
+//! across the questionnaires open so far.
+
+use crate::{
+    all_questionnaires, answers, date, inspect, moderation::highlight, parse_date, progress,
+    theme::Paint, Questionnaire,
+};
+use chrono::NaiveDate;
+use clap::ArgMatches;
+use colored::Colorize;
+use log::warn;
+use regex::{Regex, RegexBuilder};
+use reqwest::blocking::Client;
+use std::ops::Range;
+
+/// Characters shown around the first match of an answer.
+const CONTEXT: usize = 30;
+
+struct Hit {
+    username: String,
+    user_id: i32,
+    question: String,
+    snippet: String,
+    ranges: Vec<Range<usize>>,
+}
+
+/// What the search of a questionnaire got to.
+struct Found {
+    hits: Vec<Hit>,
+    /// Users whose answers couldn't be read.
+    skipped: usize,
+}
+
+pub fn command(client: &Client, args: &ArgMatches) -> bool {
+    let text = match args.value_of("text") {
+        Some(t) if !t.is_empty() => t,
+        _ => {
+            println!("{}", "What to search for?".error());
+            return false;
+        }
+    };
+    let pattern = match matcher(
+        text,
+        args.is_present("regex"),
+        args.is_present("ignore-case"),
+    ) {
+        Ok(p) => p,
+        Err(e) => {
+            println!("{}", e.error());
+            return false;
+        }
+    };
+    let since = match args.value_of("since").map(parse_date) {
+        Some(Ok(d)) => Some(d),
+        Some(Err(e)) => {
+            println!("{}", e.error());
+            return false;
+        }
+        None => None,
+    };
+    let only: Option<i32> = match args.value_of("questionnaire").map(str::parse) {
+        Some(Ok(id)) => Some(id),
+        Some(Err(_)) => {
+            println!("{}", "Invalid questionnaire id.".error());
+            return false;
+        }
+        None => None,
+    };
+
+    let questionnaires = match all_questionnaires(client) {
+        Ok(q) => q,
+        Err(()) => {
+            println!("{}", "Can't get the questionnaires.".error());
+            return false;
+        }
+    };
+    let mut searched = select(&questionnaires, only, since, date::today());
+    searched.sort_by_key(|q| q.datetime);
+    if searched.is_empty() {
+        println!("{}", "No questionnaire to search.".warning());
+        return only.is_none();
+    }
+
+    let mut hits = 0;
+    let mut users: Vec<i32> = Vec::new();
+    let mut found_in = 0;
+    let mut failed = 0;
+    let mut skipped = 0;
+    let mut bar = progress::Progress::items("Searching", searched.len() as u64);
+    let mut results = Vec::new();
+    for q in &searched {
+        if progress::cancelled() {
+            break;
+        }
+        match search(client, &q.questionnaireId.to_string(), &pattern) {
+            Ok(found) => {
+                skipped += found.skipped;
+                if !found.hits.is_empty() {
+                    results.push((*q, found.hits));
+                }
+            }
+            Err(()) => failed += 1,
+        }
+        bar.add(1);
+    }
+    drop(bar);
+
+    for (q, found) in &results {
+        println!(
+            "\n{} {} {}",
+            format!("#{}", q.questionnaireId).info(),
+            q.name.accent().bold(),
+            date::format(q.datetime).muted()
+        );
+        for hit in found {
+            println!(
+                "  {} #{}  {}",
+                hit.username.accent(),
+                hit.user_id.to_string().info(),
+                hit.question.question()
+            );
+            println!("    {}", highlight(&hit.snippet, &hit.ranges));
+            if !users.contains(&hit.user_id) {
+                users.push(hit.user_id);
+            }
+        }
+        hits += found.len();
+        found_in += 1;
+    }
+    println!(
+        "\n{} answers from {} users in {} of {} questionnaires.",
+        hits.to_string().info(),
+        users.len().to_string().info(),
+        found_in.to_string().info(),
+        searched.len()
+    );
+    if failed > 0 {
+        println!(
+            "{}",
+            format!("Can't read the answers to {} questionnaires.", failed).error()
+        );
+    }
+    if skipped > 0 {
+        println!(
+            "{}",
+            format!(
+                "Can't read the answers of {} {}, left out.",
+                skipped,
+                if skipped == 1 { "user" } else { "users" }
+            )
+            .warning()
+        );
+    }
+    if progress::cancelled() {
+        println!(
+            "{}",
+            "Cancelled: not every questionnaire was searched.".warning()
+        );
+    }
+    failed == 0 && skipped == 0
+}
+
+/// `text` as a regex, or as it is.
+fn matcher(text: &str, regex: bool, ignore_case: bool) -> Result<Regex, String> {
+    let pattern = if regex {
+        text.to_string()
+    } else {
+        regex::escape(text)
+    };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(ignore_case)
+        .build()
+        .map_err(|e| format!("Invalid regex {}: {}", text, e))
+}
+
+/// The questionnaires already open: questionnaire `only`, or the ones since
+/// `since`.
+fn select(
+    questionnaires: &[Questionnaire],
+    only: Option<i32>,
+    since: Option<NaiveDate>,
+    today: NaiveDate,
+) -> Vec<&Questionnaire> {
+    questionnaires
+        .iter()
+        .filter(|q| q.datetime <= today)
+        .filter(|q| only.is_none_or(|id| q.questionnaireId == id))
+        .filter(|q| since.is_none_or(|d| q.datetime >= d))
+        .collect()
+}
+
+/// The answers to questionnaire `id` matching `pattern`. Users whose
+/// answers can't be read are skipped.
+fn search(client: &Client, id: &str, pattern: &Regex) -> Result<Found, ()> {
+    let mut hits = Vec::new();
+    let mut skipped = 0;
+    for user in inspect(client, id, false)? {
+        if progress::cancelled() {
+            break;
+        }
+        let list = match answers(client, id, user.userId) {
+            Ok(l) => l,
+            Err(()) => {
+                warn!("Can't get the answers of {} to {}", user.username, id);
+                skipped += 1;
+                continue;
+            }
+        };
+        for answer in list.opt {
+            let found: Vec<Range<usize>> = pattern
+                .find_iter(&answer.content)
+                .map(|m| m.range())
+                .collect();
+            if found.is_empty() {
+                continue;
+            }
+            let (snippet, ranges) = snippet(&answer.content, &found, CONTEXT);
+            hits.push(Hit {
+                username: user.username.clone(),
+                user_id: user.userId,
+                question: answer.question,
+                snippet,
+                ranges,
+            });
+        }
+    }
+    Ok(Found { hits, skipped })
+}
+
+/// The part of `text` around the first of `found`, with `context` characters
+/// on each side, and the matches within it.
+fn snippet(text: &str, found: &[Range<usize>], context: usize) -> (String, Vec<Range<usize>>) {
+    let first = &found[0];
+    let start = text[..first.start]
+        .char_indices()
+        .rev()
+        .nth(context.saturating_sub(1))
+        .map_or(0, |(i, _)| i);
+    let end = text[first.end..]
+        .char_indices()
+        .nth(context)
+        .map_or(text.len(), |(i, _)| first.end + i);
+
+    let (before, after) = (
+        if start > 0 { "…" } else { "" },
+        if end < text.len() { "…" } else { "" },
+    );
+    let shift = before.len();
+    let snippet = format!("{}{}{}", before, &text[start..end], after);
+    let ranges = found
+        .iter()
+        .filter(|r| r.start >= start && r.end <= end)
+        .map(|r| r.start - start + shift..r.end - start + shift)
+        .collect();
+    (snippet, ranges)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        http,
+        stub::{self, ANNA, BOB},
+    };
+
+    #[test]
+    fn cuts_snippets_around_the_matches() {
+        let text = "I heard of it from a friend, who loves the friendly staff";
+        let pattern = matcher("FRIEND", false, true).unwrap();
+        let found: Vec<Range<usize>> = pattern.find_iter(text).map(|m| m.range()).collect();
+
+        let (s, ranges) = snippet(text, &found, 5);
+        assert_eq!(s, "…om a friend, who…");
+        let words: Vec<&str> = ranges.iter().map(|r| &s[r.clone()]).collect();
+        assert_eq!(words, ["friend"]);
+
+        let (s, ranges) = snippet(text, &found, 100);
+        assert_eq!(s, text);
+        assert_eq!(ranges.len(), 2);
+
+        assert!(matcher("FRIEND", false, false)
+            .unwrap()
+            .find(text)
+            .is_none());
+        assert!(matcher("fr(", true, false).is_err());
+        assert!(matcher("fr(", false, false).is_ok());
+    }
+
+    #[test]
+    fn searches_the_answers() {
+        let client = http::client().unwrap();
+        // carol's answers aren't there
+        let carol = r#"{"userId":3,"birth":"2001-02-03","sex":"F","username":"carol"}"#;
+        stub::users(901, false, &[ANNA, carol, BOB]);
+        stub::answers(901, 1, &[("Topping?", "Basil and ham")]);
+        stub::answers(901, 2, &[("Topping?", "Ham"), ("Crust?", "Thin")]);
+        let pattern = matcher("ham", false, true).unwrap();
+        let found = search(&client, "901", &pattern).unwrap();
+        let users: Vec<&str> = found.hits.iter().map(|h| h.username.as_str()).collect();
+        assert_eq!(users, ["anna", "bob"]);
+        assert_eq!(found.skipped, 1);
+        assert_eq!(found.hits[0].ranges, vec![Range { start: 10, end: 13 }]);
+        assert!(
+            search(&client, "901", &matcher("olive", false, true).unwrap())
+                .unwrap()
+                .hits
+                .is_empty()
+        );
+    }
+}
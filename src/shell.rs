@@ -0,0 +1,264 @@
+use clap::{App, AppSettings, Arg, ArgMatches, ArgSettings, Error, ErrorKind, YamlLoader};
+use lazy_static::lazy_static;
+use std::{iter::Peekable, str::Chars};
+use yaml_rust::Yaml;
+
+lazy_static! {
+    static ref CLI_YAML: Yaml = YamlLoader::load_from_str(include_str!("../cli.yaml"))
+        .expect("cli.yaml not readable")
+        .remove(0);
+}
+
+/// Command line definition, shared by the command line mode and the shell.
+pub fn cli() -> App<'static> {
+    App::from(&*CLI_YAML)
+        .version(env!("CARGO_PKG_VERSION"))
+        // the yaml `multiple` also makes -v take a value, `-vvv` must count
+        .mut_arg("verbose", |a| {
+            a.multiple_values(false)
+                .unset_setting(ArgSettings::TakesValue)
+        })
+}
+
+/// Command definition for the admin shell: the `admin` subcommands plus
+/// the commands that only make sense inside the shell.
+fn admin_shell() -> App<'static> {
+    cli()
+        .find_subcommand("admin")
+        .expect("admin subcommand missing from cli.yaml")
+        .clone()
+        .setting(AppSettings::NoBinaryName)
+        .global_setting(AppSettings::DisableVersion)
+        .subcommand(
+            App::new("exit")
+                .about("Leave the shell.")
+                .visible_aliases(&["back", "b", "quit"]),
+        )
+        .subcommand(
+            App::new("history")
+                .about("List past commands; run one again with !n, !! or !prefix.")
+                .arg(
+                    Arg::new("pattern")
+                        .about("Only list the commands containing this text.")
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("count")
+                        .short('n')
+                        .long("count")
+                        .value_name("N")
+                        .about("Only list the last N matching commands.")
+                        .takes_value(true),
+                ),
+        )
+}
+
+/// Parses a line typed in the admin shell.
+///
+/// Returns `Ok(None)` for blank lines; help requests come back as an `Err`
+/// of kind `DisplayHelp`, just like with `get_matches`.
+pub fn parse_admin(line: &str) -> Result<Option<ArgMatches>, Error> {
+    let toks = split(line).map_err(|e| Error::with_description(e, ErrorKind::InvalidValue))?;
+    parse_admin_words(&toks)
+}
+
+/// Same as `parse_admin`, for a line that has already been split into words.
+pub fn parse_admin_words(toks: &[String]) -> Result<Option<ArgMatches>, Error> {
+    if toks.is_empty() {
+        return Ok(None);
+    }
+    match admin_shell().try_get_matches_from(toks) {
+        // clap's help subcommand doesn't suggest anything on typos, the
+        // subcommand lookup does
+        Err(e) if e.kind == ErrorKind::UnrecognizedSubcommand && toks[0] == "help" => {
+            admin_shell().try_get_matches_from(&toks[1..]).map(Some)
+        }
+        res => res.map(Some),
+    }
+}
+
+/// Splits a line into words the way a POSIX shell would: words are separated
+/// by whitespace, `'...'` quotes literally, `"..."` quotes allowing `\"`,
+/// `\\` and `\$` escapes, a backslash outside quotes escapes the next
+/// character and a `#` starting a word comments out the rest of the line.
+pub fn split(line: &str) -> Result<Vec<String>, String> {
+    tokenize(line, None)
+}
+
+/// Variable lookup used when expanding `$NAME`.
+pub type Lookup<'a> = dyn Fn(&str) -> Option<String> + 'a;
+
+/// Like `split`, additionally replacing `$NAME` and `${NAME}` outside of
+/// single quotes with the value returned by `lookup`.
+pub fn split_expanding(line: &str, lookup: &Lookup<'_>) -> Result<Vec<String>, String> {
+    tokenize(line, Some(lookup))
+}
+
+fn tokenize(line: &str, lookup: Option<&Lookup<'_>>) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    // distinguishes "no word yet" from an empty quoted word like ''
+    let mut in_word = false;
+    let mut chars = line.chars().peekable();
+
+    // expands the variable whose `$` was just consumed
+    let expand = |chars: &mut Peekable<Chars>, word: &mut String| -> Result<(), String> {
+        let lookup = match lookup {
+            Some(l) => l,
+            None => {
+                word.push('$');
+                return Ok(());
+            }
+        };
+        let mut name = String::new();
+        if chars.peek() == Some(&'{') {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('}') => break,
+                    Some(c) => name.push(c),
+                    None => return Err("unterminated ${".to_string()),
+                }
+            }
+        } else {
+            while let Some(&c) = chars.peek() {
+                if !(c.is_ascii_alphanumeric() || c == '_') {
+                    break;
+                }
+                name.push(c);
+                chars.next();
+            }
+        }
+        if name.is_empty() {
+            word.push('$');
+            return Ok(());
+        }
+        match lookup(&name) {
+            Some(v) => {
+                word.push_str(&v);
+                Ok(())
+            }
+            None => Err(format!("undefined variable {}", name)),
+        }
+    };
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            '#' if !in_word => break,
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated single quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c) if c == '"' || c == '\\' || c == '$' => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err("unterminated double quote".to_string()),
+                        },
+                        Some('$') => expand(&mut chars, &mut word)?,
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated double quote".to_string()),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                match chars.next() {
+                    Some(c) => word.push(c),
+                    None => return Err("trailing backslash".to_string()),
+                }
+            }
+            '$' => {
+                in_word = true;
+                expand(&mut chars, &mut word)?;
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(line: &str) -> Vec<String> {
+        split(line).unwrap()
+    }
+
+    #[test]
+    fn splits_like_a_shell() {
+        assert_eq!(words("  list  -s 10 "), ["list", "-s", "10"]);
+        assert_eq!(
+            words(r#"create -n 'Pizza "Night"' -q "It's \"good\"?""#),
+            ["create", "-n", r#"Pizza "Night""#, "-q", r#"It's "good"?"#]
+        );
+        assert_eq!(words(r"a\ b c\#d '' x"), ["a b", "c#d", "", "x"]);
+        assert_eq!(words(r#""\n\$\\""#), [r"\n$\"]);
+        assert_eq!(words("delete 3 # not 4"), ["delete", "3"]);
+        assert_eq!(words("# nothing"), Vec::<String>::new());
+        assert_eq!(words("a#b"), ["a#b"]);
+
+        assert!(split("say 'hi").is_err());
+        assert!(split("say \"hi").is_err());
+        assert!(split("say hi\\").is_err());
+    }
+
+    #[test]
+    fn expands_variables() {
+        let lookup = |name: &str| match name {
+            "ID" => Some("3".to_string()),
+            "NAME" => Some("Pizza Night".to_string()),
+            _ => None,
+        };
+        let words = |line| split_expanding(line, &lookup);
+        assert_eq!(
+            words(r#"edit $ID -n "$NAME" '$NAME'"#).unwrap(),
+            ["edit", "3", "-n", "Pizza Night", "$NAME"]
+        );
+        assert_eq!(words("x${ID}y $ \\$ID").unwrap(), ["x3y", "$", "$ID"]);
+        assert!(words("delete $MISSING").is_err());
+        assert!(words("delete ${ID").is_err());
+        assert_eq!(split("delete $ID").unwrap(), ["delete", "$ID"]);
+    }
+
+    #[test]
+    fn parses_admin_lines() {
+        assert!(parse_admin("   ").unwrap().is_none());
+        let m = parse_admin("delete 3 4").unwrap().unwrap();
+        assert_eq!(m.subcommand_name(), Some("delete"));
+        assert!(parse_admin("delete 'oops").is_err());
+
+        let help = parse_admin("help list").unwrap_err();
+        assert_eq!(help.kind, ErrorKind::DisplayHelp);
+        // a typo gets a suggestion rather than a bare "unrecognized"
+        let typo = parse_admin("help lsit").unwrap_err();
+        assert_eq!(typo.kind, ErrorKind::InvalidSubcommand);
+        assert!(typo.to_string().contains("list"));
+    }
+}
@@ -0,0 +1,146 @@
+//! HTTP server standing in for GamifyUser in the tests, at the base link of
+//! tests/config.yaml. Each test registers the responses it expects to get,
+//! keyed by method and URL relative to the base link, so tests running in
+//! parallel must use URLs of their own.
+
+use crate::{CONFIG, ENDPOINT};
+use lazy_static::lazy_static;
+use reqwest::Url;
+use std::{
+    collections::HashMap,
+    sync::{Mutex, Once},
+    thread,
+    time::Duration,
+};
+use tiny_http::{Response, Server};
+
+#[derive(Clone)]
+struct Stubbed {
+    status: u16,
+    body: String,
+    delay: Duration,
+}
+
+lazy_static! {
+    static ref RESPONSES: Mutex<HashMap<(String, String), Stubbed>> = Mutex::new(HashMap::new());
+    static ref HITS: Mutex<HashMap<(String, String), usize>> = Mutex::new(HashMap::new());
+    static ref BODIES: Mutex<HashMap<(String, String), String>> = Mutex::new(HashMap::new());
+}
+static START: Once = Once::new();
+
+/// Answers `method url` with `status` and `body` from now on.
+pub fn on(method: &str, url: &str, status: u16, body: &str) {
+    on_after(method, url, status, body, Duration::from_secs(0));
+}
+
+/// Same as `on`, answering only after `delay`.
+pub fn on_after(method: &str, url: &str, status: u16, body: &str, delay: Duration) {
+    START.call_once(start);
+    RESPONSES.lock().unwrap().insert(
+        (method.to_string(), url.to_string()),
+        Stubbed {
+            status,
+            body: body.to_string(),
+            delay,
+        },
+    );
+}
+
+/// Times `method url` was requested.
+pub fn hits(method: &str, url: &str) -> usize {
+    *HITS
+        .lock()
+        .unwrap()
+        .get(&(method.to_string(), url.to_string()))
+        .unwrap_or(&0)
+}
+
+/// Body of the last `method url` request, if any.
+pub fn body(method: &str, url: &str) -> Option<String> {
+    BODIES
+        .lock()
+        .unwrap()
+        .get(&(method.to_string(), url.to_string()))
+        .cloned()
+}
+
+/// Users for the fixtures below.
+pub const ANNA: &str = r#"{"userId":1,"birth":"1990-05-04","sex":"F","username":"anna"}"#;
+pub const BOB: &str = r#"{"userId":2,"birth":"1985-11-23","sex":"M","username":"bob"}"#;
+
+/// URL of the (first page of) users who completed, or canceled,
+/// questionnaire `id`.
+pub fn users_url(id: i32, canceled: bool) -> String {
+    let endpoint = if canceled {
+        ENDPOINT::ADMIN_INSPECT_CANCELED
+    } else {
+        ENDPOINT::ADMIN_INSPECT
+    };
+    format!("{}?id={}&start=0&size=100", endpoint, id)
+}
+
+/// Answers that `users`, e.g. `[ANNA, BOB]`, completed (or canceled)
+/// questionnaire `id`.
+pub fn users(id: i32, canceled: bool, users: &[&str]) {
+    let body = format!("[{}]", users.join(","));
+    on("GET", &users_url(id, canceled), 200, &body);
+}
+
+pub fn answers_url(id: i32, user: i32) -> String {
+    format!(
+        "{}?questionnaireId={}&userId={}",
+        ENDPOINT::ADMIN_ANSWERS_RETRIEVAL,
+        id,
+        user
+    )
+}
+
+/// Answers that `user` answered questionnaire `id` with `opt`, pairs of
+/// question and answer, without any statistical answer.
+pub fn answers(id: i32, user: i32, opt: &[(&str, &str)]) {
+    let opt: Vec<serde_json::Value> = opt
+        .iter()
+        .map(|(q, a)| serde_json::json!({ "question": q, "content": a }))
+        .collect();
+    let body = serde_json::json!({ "stats": [], "opt": opt }).to_string();
+    on("GET", &answers_url(id, user), 200, &body);
+}
+
+fn start() {
+    let base = Url::parse(&CONFIG.base_link).unwrap();
+    let server = Server::http(format!(
+        "{}:{}",
+        base.host_str().unwrap(),
+        base.port().unwrap()
+    ))
+    .expect("Can't start the stub server");
+    let prefix = base.path().to_string();
+
+    thread::spawn(move || {
+        for mut request in server.incoming_requests() {
+            let key = (
+                request.method().to_string(),
+                request.url().trim_start_matches(&prefix).to_string(),
+            );
+            *HITS.lock().unwrap().entry(key.clone()).or_insert(0) += 1;
+            let mut body = Vec::new();
+            let _ = request.as_reader().read_to_end(&mut body);
+            BODIES
+                .lock()
+                .unwrap()
+                .insert(key.clone(), String::from_utf8_lossy(&body).into_owned());
+            let stubbed = RESPONSES.lock().unwrap().get(&key).cloned();
+            // slow answers must not hold up the other tests
+            thread::spawn(move || {
+                let response = match stubbed {
+                    Some(s) => {
+                        thread::sleep(s.delay);
+                        Response::from_string(s.body).with_status_code(s.status)
+                    }
+                    None => Response::from_string("Not stubbed").with_status_code(404),
+                };
+                let _ = request.respond(response);
+            });
+        }
+    });
+}
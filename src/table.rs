@@ -0,0 +1,503 @@
+//! Box-drawn tables fitting the terminal: columns are as wide as their
+//! content, shrinking (truncated or wrapped) when the terminal is too narrow.
+//! Widths are measured on screen, ignoring color codes, and the terminal
+//! size is read at every print so tables follow resizes.
+
+use crate::{theme::Paint, TERMINAL_DIMENSIONS};
+use clap::ArgMatches;
+use colored::ColoredString;
+use std::cmp::Ordering;
+use unicode_width::UnicodeWidthChar;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+pub struct Column {
+    name: String,
+    align: Align,
+    wrap: bool,
+    style: Option<fn(&str) -> ColoredString>,
+}
+
+impl Column {
+    pub fn new(name: &str) -> Column {
+        Column {
+            name: name.to_string(),
+            align: Align::Center,
+            wrap: false,
+            style: None,
+        }
+    }
+
+    pub fn align(mut self, align: Align) -> Column {
+        self.align = align;
+        self
+    }
+
+    /// Wraps long cells on several lines instead of truncating them.
+    pub fn wrap(mut self) -> Column {
+        self.wrap = true;
+        self
+    }
+
+    /// Colors the cells of this column.
+    pub fn style(mut self, style: fn(&str) -> ColoredString) -> Column {
+        self.style = Some(style);
+        self
+    }
+}
+
+pub struct Cell {
+    text: String,
+    key: String,
+}
+
+impl Cell {
+    /// Sorts by `key` instead of the text shown, e.g. dates as YYYY-MM-DD.
+    pub fn sorted_as(mut self, key: impl ToString) -> Cell {
+        self.key = key.to_string();
+        self
+    }
+}
+
+impl<T: ToString> From<T> for Cell {
+    fn from(text: T) -> Cell {
+        let text = text.to_string();
+        Cell {
+            key: strip_ansi(&text),
+            text,
+        }
+    }
+}
+
+struct Row {
+    index: usize,
+    cells: Vec<Cell>,
+}
+
+pub struct Table {
+    columns: Vec<Column>,
+    rows: Vec<Row>,
+}
+
+impl Table {
+    pub fn new(columns: Vec<Column>) -> Table {
+        Table {
+            columns,
+            rows: Vec::new(),
+        }
+    }
+
+    /// Adds a row, with a cell per column.
+    pub fn add_row(&mut self, cells: Vec<Cell>) {
+        debug_assert_eq!(cells.len(), self.columns.len());
+        self.rows.push(Row {
+            index: self.rows.len(),
+            cells,
+        });
+    }
+
+    fn column(&self, name: &str) -> Result<usize, String> {
+        self.columns
+            .iter()
+            .position(|c| c.name.eq_ignore_ascii_case(name.trim()))
+            .ok_or_else(|| {
+                let names: Vec<String> =
+                    self.columns.iter().map(|c| c.name.to_lowercase()).collect();
+                format!(
+                    "No column {}, pick among {}.",
+                    name.trim(),
+                    names.join(", ")
+                )
+            })
+    }
+
+    /// Keeps only the comma separated `names` columns, in that order.
+    pub fn select(&mut self, names: &str) -> Result<(), String> {
+        let picked = names
+            .split(',')
+            .filter(|n| !n.trim().is_empty())
+            .map(|n| self.column(n))
+            .collect::<Result<Vec<usize>, String>>()?;
+        if picked.is_empty() {
+            return Err("No columns given.".to_string());
+        }
+
+        let mut columns: Vec<Option<Column>> = self.columns.drain(..).map(Some).collect();
+        self.columns = picked.iter().filter_map(|&i| columns[i].take()).collect();
+        for row in &mut self.rows {
+            let mut cells: Vec<Option<Cell>> = row.cells.drain(..).map(Some).collect();
+            row.cells = picked.iter().filter_map(|&i| cells[i].take()).collect();
+        }
+        Ok(())
+    }
+
+    /// Sorts the rows by column `name`, numerically if its cells are numbers.
+    pub fn sort_by(&mut self, name: &str, reverse: bool) -> Result<(), String> {
+        let i = self.column(name)?;
+        self.rows.sort_by(|a, b| {
+            let (a, b) = (&a.cells[i].key, &b.cells[i].key);
+            let order = match (a.parse::<f64>(), b.parse::<f64>()) {
+                (Ok(x), Ok(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+                _ => a.to_lowercase().cmp(&b.to_lowercase()),
+            };
+            if reverse {
+                order.reverse()
+            } else {
+                order
+            }
+        });
+        Ok(())
+    }
+
+    /// Applies the `--columns`, `--sort` and `--reverse` arguments, printing
+    /// what's wrong with them if anything.
+    pub fn configure(&mut self, args: &ArgMatches) -> bool {
+        let mut result = Ok(());
+        if let Some(names) = args.value_of("columns") {
+            result = self.select(names);
+        }
+        if let (Ok(()), Some(name)) = (&result, args.value_of("sort")) {
+            result = self.sort_by(name, args.is_present("reverse"));
+        }
+        match result {
+            Ok(()) => true,
+            Err(e) => {
+                println!("{}", e.error());
+                false
+            }
+        }
+    }
+
+    /// Where each row was added, in the order they are shown.
+    pub fn order(&self) -> Vec<usize> {
+        self.rows.iter().map(|r| r.index).collect()
+    }
+
+    /// Widths of the columns, fitting `max` if possible.
+    fn widths(&self, max: usize) -> Vec<usize> {
+        let mut widths: Vec<usize> = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                self.rows
+                    .iter()
+                    .map(|r| display_width(&r.cells[i].text))
+                    .chain(Some(display_width(&c.name) + 2))
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        // the widest column gives up one character at a time, down to its name
+        let borders = 3 * widths.len() + 1;
+        while widths.iter().sum::<usize>() + borders > max {
+            let widest = widths
+                .iter()
+                .enumerate()
+                .filter(|(i, w)| **w > display_width(&self.columns[*i].name) + 2)
+                .max_by_key(|(_, w)| **w)
+                .map(|(i, _)| i);
+            match widest {
+                Some(i) => widths[i] -= 1,
+                None => break,
+            }
+        }
+        widths
+    }
+
+    /// Border line with the names of the columns, e.g. `┌─ ID ─┬─ Name ─┐`.
+    fn border(&self, widths: &[usize], left: &str, middle: &str, right: &str) -> String {
+        let names: Vec<String> = self
+            .columns
+            .iter()
+            .zip(widths)
+            .map(|(c, w)| {
+                let name = format!(" {} ", truncate(&c.name, w.saturating_sub(2)));
+                pad(&name, w + 2, Align::Center, '─')
+            })
+            .collect();
+        format!("{}{}{}", left, names.join(middle), right)
+    }
+
+    fn lines(&self, row: &Row, widths: &[usize]) -> Vec<String> {
+        let cells: Vec<Vec<String>> = self
+            .columns
+            .iter()
+            .zip(&row.cells)
+            .zip(widths)
+            .map(|((c, cell), &w)| {
+                if c.wrap {
+                    wrap(&strip_ansi(&cell.text), w)
+                } else {
+                    vec![truncate(&cell.text, w)]
+                }
+            })
+            .collect();
+        let height = cells.iter().map(Vec::len).max().unwrap_or(1);
+
+        (0..height)
+            .map(|line| {
+                let parts: Vec<String> = self
+                    .columns
+                    .iter()
+                    .zip(&cells)
+                    .zip(widths)
+                    .map(|((c, lines), &w)| {
+                        let text = lines.get(line).map(String::as_str).unwrap_or("");
+                        let text = pad(text, w, c.align, ' ');
+                        match c.style {
+                            Some(style) => style(&text).to_string(),
+                            None => text,
+                        }
+                    })
+                    .collect();
+                format!("│ {} │", parts.join(" │ "))
+            })
+            .collect()
+    }
+
+    /// The top border, as a header for the rows.
+    pub fn header(&self, max: usize) -> String {
+        self.border(&self.widths(max), "┌", "┬", "┐")
+    }
+
+    /// The rows, in order, each on as many lines as it takes.
+    pub fn rows(&self, max: usize) -> Vec<String> {
+        let widths = self.widths(max);
+        self.rows
+            .iter()
+            .map(|r| self.lines(r, &widths).join("\n"))
+            .collect()
+    }
+
+    /// Every line of the table.
+    pub fn render(&self, max: usize) -> Vec<String> {
+        let widths = self.widths(max);
+        let mut lines = vec![self.border(&widths, "┌", "┬", "┐")];
+        for row in &self.rows {
+            lines.extend(self.lines(row, &widths));
+        }
+        lines.push(self.border(&widths, "└", "┴", "┘"));
+        lines
+    }
+
+    /// Prints the table centered in the terminal.
+    pub fn print(&self) {
+        let max = width();
+        let lines = self.render(max);
+        let indent = lines
+            .first()
+            .map_or(0, |l| max.saturating_sub(display_width(l)) / 2);
+        for line in lines {
+            println!("{:indent$}{}", "", line, indent = indent);
+        }
+    }
+}
+
+/// Current width of the terminal.
+pub fn width() -> usize {
+    term_size::dimensions()
+        .map(|(w, _)| w)
+        .unwrap_or(TERMINAL_DIMENSIONS.0)
+}
+
+/// `s` without its color codes.
+pub fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // CSI sequences end with a letter, e.g. \x1b[1;34m
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Columns `s` takes on screen.
+pub fn display_width(s: &str) -> usize {
+    strip_ansi(s).chars().filter_map(|c| c.width()).sum()
+}
+
+/// `s` cut to `width` columns, ending with `…` if it was longer. Color codes
+/// are kept, and reset if it was cut.
+pub fn truncate(s: &str, width: usize) -> String {
+    if display_width(s) <= width {
+        return s.to_string();
+    }
+    let mut out = String::new();
+    let mut used = 0;
+    let mut colored = false;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            colored = true;
+            out.push(c);
+            for c in chars.by_ref() {
+                out.push(c);
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+            continue;
+        }
+        let w = c.width().unwrap_or(0);
+        if used + w + 1 > width {
+            break;
+        }
+        used += w;
+        out.push(c);
+    }
+    if width > 0 {
+        out.push('…');
+    }
+    if colored {
+        out.push_str("\x1b[0m");
+    }
+    out
+}
+
+/// Plain `text` wrapped on lines of at most `width` columns, breaking words
+/// only when longer than a line.
+pub fn wrap(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let needed = if line.is_empty() { 0 } else { 1 } + display_width(word);
+        if !line.is_empty() && display_width(&line) + needed > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        for c in word.chars() {
+            let w = c.width().unwrap_or(0);
+            if display_width(&line) + w > width {
+                lines.push(std::mem::take(&mut line));
+            }
+            line.push(c);
+        }
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// `s` padded with `fill` to `width` columns.
+pub fn pad(s: &str, width: usize, align: Align, fill: char) -> String {
+    let missing = width.saturating_sub(display_width(s));
+    let (left, right) = match align {
+        Align::Left => (0, missing),
+        Align::Right => (missing, 0),
+        Align::Center => (missing / 2, missing - missing / 2),
+    };
+    let fill = |n: usize| fill.to_string().repeat(n);
+    format!("{}{}{}", fill(left), s, fill(right))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> Table {
+        let mut t = Table::new(vec![
+            Column::new("ID").align(Align::Right),
+            Column::new("Name").align(Align::Left).wrap(),
+            Column::new("Date"),
+        ]);
+        t.add_row(vec![
+            Cell::from(10),
+            "Pizza".into(),
+            Cell::from("Jan 10").sorted_as("2021-01-10"),
+        ]);
+        t.add_row(vec![
+            Cell::from(2),
+            "Noise cancelling headphones".into(),
+            Cell::from("Feb 1").sorted_as("2021-02-01"),
+        ]);
+        t
+    }
+
+    #[test]
+    fn measures_without_color_codes() {
+        let blue = "\x1b[1;34mPizza\x1b[0m";
+        assert!(blue.len() > 5);
+        assert_eq!(display_width(blue), 5);
+        assert_eq!(strip_ansi(blue), "Pizza");
+        assert_eq!(display_width("寿司"), 4);
+    }
+
+    #[test]
+    fn truncates_keeping_colors() {
+        assert_eq!(truncate("Pizza", 5), "Pizza");
+        assert_eq!(truncate("Pizza", 4), "Piz…");
+        assert_eq!(truncate("寿司寿司", 5), "寿司…");
+        let cut = truncate("\x1b[34mPizza\x1b[0m", 3);
+        assert_eq!(strip_ansi(&cut), "Pi…");
+        assert!(cut.ends_with("\x1b[0m"));
+    }
+
+    #[test]
+    fn wraps_on_words() {
+        assert_eq!(
+            wrap("Noise cancelling headphones", 12),
+            ["Noise", "cancelling", "headphones"]
+        );
+        assert_eq!(wrap("a b c", 3), ["a b", "c"]);
+        assert_eq!(
+            wrap("Supercalifragilistic", 8),
+            ["Supercal", "ifragili", "stic"]
+        );
+        assert_eq!(wrap("", 8), [""]);
+    }
+
+    #[test]
+    fn fits_the_content_when_there_is_room() {
+        let lines = table().render(200);
+        assert_eq!(
+            lines,
+            [
+                "┌─ ID ─┬─────────── Name ────────────┬─ Date ─┐",
+                "│   10 │ Pizza                       │ Jan 10 │",
+                "│    2 │ Noise cancelling headphones │ Feb 1  │",
+                "└─ ID ─┴─────────── Name ────────────┴─ Date ─┘",
+            ]
+        );
+    }
+
+    #[test]
+    fn shrinks_to_the_terminal() {
+        let lines = table().render(30);
+        assert!(lines.iter().all(|l| display_width(l) <= 30));
+        assert_eq!(lines[2], "│    2 │ Noise      │ Feb 1  │");
+        assert_eq!(lines[3], "│      │ cancelling │        │");
+        // can't get narrower than the column names
+        assert!(table().render(5).iter().all(|l| display_width(l) == 26));
+    }
+
+    #[test]
+    fn selects_and_sorts_columns() {
+        let mut t = table();
+        t.sort_by("id", false).unwrap();
+        assert_eq!(t.order(), [1, 0]);
+        t.sort_by("date", true).unwrap();
+        assert_eq!(t.order(), [1, 0]);
+        t.select("date, id").unwrap();
+        assert_eq!(t.render(200)[1], "│ Feb 1  │    2 │");
+        assert!(t.select("name").is_err());
+        assert!(t.sort_by("nope", false).is_err());
+    }
+}
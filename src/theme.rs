@@ -0,0 +1,284 @@
+//! Colors of the output, by what the text is rather than by color, so that
+//! they can follow the `theme` from the config.
+//!
+//! Colors and banners go away with `--plain`, when `NO_COLOR` is set or when
+//! stdout isn't a terminal.
+
+use colored::{Color, ColoredString, Colorize};
+use std::{
+    env,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+/// What a piece of text is.
+#[derive(Clone, Copy)]
+pub enum Role {
+    /// Titles, names and the like.
+    Accent,
+    /// Prompts, ids and counts.
+    Info,
+    Link,
+    /// Hints.
+    Muted,
+    Success,
+    Error,
+    Warning,
+    /// Statistical answers.
+    Stat,
+    Question,
+    Answer,
+    /// Lines between sections, meant to be padded.
+    Separator,
+}
+
+#[derive(Clone, Copy)]
+struct Style {
+    fg: Option<Color>,
+    bg: Option<Color>,
+    bold: bool,
+    italic: bool,
+    reversed: bool,
+}
+
+const NONE: Style = Style {
+    fg: None,
+    bg: None,
+    bold: false,
+    italic: false,
+    reversed: false,
+};
+
+const fn fg(color: Color) -> Style {
+    Style {
+        fg: Some(color),
+        ..NONE
+    }
+}
+
+const fn bold(style: Style) -> Style {
+    Style {
+        bold: true,
+        ..style
+    }
+}
+
+const fn bg(color: Color) -> Style {
+    Style {
+        bg: Some(color),
+        ..NONE
+    }
+}
+
+struct Theme {
+    name: &'static str,
+    /// By Role, in the same order.
+    styles: [Style; 11],
+}
+
+const THEMES: [Theme; 4] = [
+    Theme {
+        name: "default",
+        styles: [
+            fg(Color::BrightBlue),
+            fg(Color::Blue),
+            fg(Color::Magenta),
+            Style {
+                italic: true,
+                ..fg(Color::White)
+            },
+            fg(Color::BrightGreen),
+            fg(Color::Red),
+            bold(fg(Color::Yellow)),
+            fg(Color::BrightMagenta),
+            fg(Color::BrightYellow),
+            fg(Color::BrightWhite),
+            bg(Color::Magenta),
+        ],
+    },
+    // no bright yellow or white, unreadable on a white background
+    Theme {
+        name: "light-terminal",
+        styles: [
+            bold(fg(Color::Blue)),
+            fg(Color::Blue),
+            fg(Color::Magenta),
+            Style {
+                italic: true,
+                ..fg(Color::BrightBlack)
+            },
+            fg(Color::Green),
+            fg(Color::Red),
+            bold(fg(Color::Magenta)),
+            fg(Color::Magenta),
+            bold(fg(Color::Blue)),
+            NONE,
+            bg(Color::BrightBlack),
+        ],
+    },
+    Theme {
+        name: "high-contrast",
+        styles: [
+            bold(fg(Color::BrightWhite)),
+            bold(fg(Color::BrightCyan)),
+            bold(fg(Color::BrightYellow)),
+            fg(Color::White),
+            bold(fg(Color::BrightGreen)),
+            bold(fg(Color::BrightRed)),
+            bold(fg(Color::BrightYellow)),
+            bold(fg(Color::BrightCyan)),
+            bold(fg(Color::BrightYellow)),
+            bold(fg(Color::BrightWhite)),
+            bg(Color::White),
+        ],
+    },
+    // emphasis only, for terminals without colors
+    Theme {
+        name: "monochrome",
+        styles: [
+            bold(NONE),
+            NONE,
+            NONE,
+            Style {
+                italic: true,
+                ..NONE
+            },
+            bold(NONE),
+            bold(NONE),
+            bold(NONE),
+            bold(NONE),
+            bold(NONE),
+            NONE,
+            Style {
+                reversed: true,
+                ..NONE
+            },
+        ],
+    },
+];
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PLAIN: AtomicBool = AtomicBool::new(false);
+
+/// Switches to the theme called `name`.
+pub fn set(name: &str) -> Result<(), String> {
+    match THEMES.iter().position(|t| t.name == name) {
+        Some(i) => {
+            CURRENT.store(i, Ordering::Relaxed);
+            Ok(())
+        }
+        None => {
+            let names: Vec<&str> = THEMES.iter().map(|t| t.name).collect();
+            Err(format!(
+                "Unknown theme {}, pick among {}.",
+                name,
+                names.join(", ")
+            ))
+        }
+    }
+}
+
+/// Turns colors and banners off if asked to with `--plain`, `NO_COLOR`, or
+/// if the output isn't a terminal.
+pub fn init(plain: bool) {
+    let no_color = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    if plain || no_color || !atty::is(atty::Stream::Stdout) {
+        PLAIN.store(true, Ordering::Relaxed);
+        colored::control::set_override(false);
+    }
+}
+
+/// Whether the output is plain text: no colors, no banners.
+pub fn is_plain() -> bool {
+    PLAIN.load(Ordering::Relaxed)
+}
+
+/// Text painted after its role in the current theme.
+pub trait Paint {
+    fn paint(&self, role: Role) -> ColoredString;
+
+    fn accent(&self) -> ColoredString {
+        self.paint(Role::Accent)
+    }
+    fn info(&self) -> ColoredString {
+        self.paint(Role::Info)
+    }
+    fn link(&self) -> ColoredString {
+        self.paint(Role::Link)
+    }
+    fn muted(&self) -> ColoredString {
+        self.paint(Role::Muted)
+    }
+    fn success(&self) -> ColoredString {
+        self.paint(Role::Success)
+    }
+    fn error(&self) -> ColoredString {
+        self.paint(Role::Error)
+    }
+    fn warning(&self) -> ColoredString {
+        self.paint(Role::Warning)
+    }
+    fn stat(&self) -> ColoredString {
+        self.paint(Role::Stat)
+    }
+    fn question(&self) -> ColoredString {
+        self.paint(Role::Question)
+    }
+    fn answer(&self) -> ColoredString {
+        self.paint(Role::Answer)
+    }
+    fn separator(&self) -> ColoredString {
+        self.paint(Role::Separator)
+    }
+}
+
+impl Paint for str {
+    fn paint(&self, role: Role) -> ColoredString {
+        let style = THEMES[CURRENT.load(Ordering::Relaxed)].styles[role as usize];
+        let mut s = self.normal();
+        if let Some(c) = style.fg {
+            s = s.color(c);
+        }
+        if let Some(c) = style.bg {
+            s = s.on_color(c);
+        }
+        if style.bold {
+            s = s.bold();
+        }
+        if style.italic {
+            s = s.italic();
+        }
+        if style.reversed {
+            s = s.reversed();
+        }
+        s
+    }
+}
+
+impl Paint for String {
+    fn paint(&self, role: Role) -> ColoredString {
+        self.as_str().paint(role)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn knows_its_themes() {
+        for t in &THEMES {
+            assert!(set(t.name).is_ok());
+        }
+        assert!(set("solarized").unwrap_err().contains("monochrome"));
+        set("default").unwrap();
+    }
+
+    #[test]
+    fn monochrome_has_no_colors() {
+        let theme = THEMES.iter().find(|t| t.name == "monochrome").unwrap();
+        assert!(theme
+            .styles
+            .iter()
+            .all(|s| s.fg.is_none() && s.bg.is_none()));
+    }
+}
@@ -0,0 +1,300 @@
+//! The `watch` command: who completes and who cancels a questionnaire while
+//! it runs, asked to the server again every few seconds until Ctrl-C.
+
+use crate::{
+    all_questionnaires, cache, date, inspect, progress, prompt, table, theme::Paint, Questionnaire,
+    User,
+};
+use chrono::Local;
+use clap::ArgMatches;
+use colored::Colorize;
+use dialoguer::console::Term;
+use reqwest::blocking::Client;
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Seconds between two refreshes, unless `--interval` says otherwise.
+const INTERVAL: u64 = 10;
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// What was seen of the questionnaire so far.
+#[derive(Default)]
+struct Dashboard {
+    completed: Vec<i32>,
+    canceled: Vec<i32>,
+    /// Users who showed up at the last refresh, and whether they completed
+    /// the questionnaire.
+    new: Vec<(String, bool)>,
+    /// How many showed up at each refresh after the first, oldest first.
+    history: Vec<usize>,
+    refreshes: usize,
+}
+
+impl Dashboard {
+    /// Takes in the users of a refresh. The ones already there at the first
+    /// refresh aren't new.
+    fn update(&mut self, completed: &[User], canceled: &[User]) {
+        let mut new = Vec::new();
+        for (users, seen, done) in [
+            (completed, &self.completed, true),
+            (canceled, &self.canceled, false),
+        ] {
+            new.extend(
+                users
+                    .iter()
+                    .filter(|u| !seen.contains(&u.userId))
+                    .map(|u| (u.username.clone(), done)),
+            );
+        }
+        if self.refreshes > 0 {
+            self.history.push(new.len());
+            self.new = new;
+        }
+        self.completed = completed.iter().map(|u| u.userId).collect();
+        self.canceled = canceled.iter().map(|u| u.userId).collect();
+        self.refreshes += 1;
+    }
+
+    /// Share of the users who canceled, in percent.
+    fn cancellation_rate(&self) -> Option<usize> {
+        let total = self.completed.len() + self.canceled.len();
+        Some(total)
+            .filter(|&t| t > 0)
+            .map(|t| (self.canceled.len() * 100 + t / 2) / t)
+    }
+
+    fn render(&self, q: &Questionnaire, width: usize) -> Vec<String> {
+        let rate = match self.cancellation_rate() {
+            Some(r) => format!("{}%", r),
+            None => "–".to_string(),
+        };
+        let new = if self.new.is_empty() {
+            "Nobody new since the last refresh.".muted().to_string()
+        } else {
+            let names: Vec<String> = self
+                .new
+                .iter()
+                .map(|(name, done)| {
+                    if *done {
+                        name.accent().to_string()
+                    } else {
+                        format!("{} {}", name.accent(), "(canceled)".muted())
+                    }
+                })
+                .collect();
+            format!("New: {}", names.join(", "))
+        };
+        let label = "Responses  ";
+        let spark = sparkline(&self.history, width.saturating_sub(label.len() + 2));
+        vec![
+            format!(
+                "{} {} {}",
+                format!("#{}", q.questionnaireId).info(),
+                q.name.accent().bold(),
+                date::format(q.datetime).muted()
+            ),
+            String::new(),
+            format!("  Completed  {}", self.completed.len().to_string().stat()),
+            format!("  Canceled   {}", self.canceled.len().to_string().stat()),
+            format!("  Cancellation rate  {}", rate.stat()),
+            String::new(),
+            table::truncate(&format!("  {}", new), width),
+            format!("  {}{}", label, spark.info()),
+        ]
+    }
+}
+
+pub fn command(client: &Client, args: &ArgMatches) -> bool {
+    let interval = match args.value_of("interval").map(str::parse::<u64>) {
+        Some(Ok(s)) if s > 0 => Duration::from_secs(s),
+        Some(_) => {
+            println!("{}", "Invalid interval, expected seconds.".error());
+            return false;
+        }
+        None => Duration::from_secs(INTERVAL),
+    };
+    let questionnaires = match all_questionnaires(client) {
+        Ok(q) => q,
+        Err(()) => {
+            println!("{}", "Can't get the questionnaires.".error());
+            return false;
+        }
+    };
+    let q = match pick(&questionnaires, args.value_of("id")) {
+        Ok(q) => q,
+        Err(e) => {
+            println!("{}", e.error());
+            return false;
+        }
+    };
+    let id = q.questionnaireId.to_string();
+
+    let term = Term::stdout();
+    let live = term.features().is_attended();
+    let mut dashboard = Dashboard::default();
+    let started = Instant::now();
+    loop {
+        let refresh = cache::fresh(|| {
+            Ok::<_, ()>((inspect(client, &id, false)?, inspect(client, &id, true)?))
+        });
+        let failed = match refresh {
+            Ok((completed, canceled)) => {
+                dashboard.update(&completed, &canceled);
+                false
+            }
+            Err(()) if dashboard.refreshes == 0 => {
+                println!("{}", format!("Can't get the users of {}.", id).error());
+                return false;
+            }
+            Err(()) => true,
+        };
+        if progress::cancelled() {
+            break;
+        }
+
+        let mut lines = dashboard.render(q, table::width());
+        lines.push(String::new());
+        if failed {
+            lines.push(
+                "Can't reach the server, these are the last counts."
+                    .warning()
+                    .to_string(),
+            );
+        }
+        lines.push(
+            format!(
+                "Refreshed at {}, every {} s. Ctrl-C to stop.",
+                Local::now().format("%H:%M:%S"),
+                interval.as_secs()
+            )
+            .muted()
+            .to_string(),
+        );
+        if live {
+            let _ = term.clear_screen();
+        }
+        for line in &lines {
+            println!("{}", line);
+        }
+        if !live {
+            println!();
+        }
+
+        let asked = Instant::now();
+        while asked.elapsed() < interval && !progress::cancelled() {
+            thread::sleep(Duration::from_millis(100));
+        }
+        if progress::cancelled() {
+            break;
+        }
+    }
+
+    println!(
+        "\n{} new respondents in {} min.",
+        dashboard.history.iter().sum::<usize>().to_string().info(),
+        started.elapsed().as_secs() / 60
+    );
+    true
+}
+
+/// Questionnaire `id`, or else today's, asking which one if there are
+/// several.
+fn pick<'q>(
+    questionnaires: &'q [Questionnaire],
+    id: Option<&str>,
+) -> Result<&'q Questionnaire, String> {
+    if let Some(id) = id {
+        let wanted: i32 = id
+            .parse()
+            .map_err(|_| "Invalid questionnaire id.".to_string())?;
+        return questionnaires
+            .iter()
+            .find(|q| q.questionnaireId == wanted)
+            .ok_or_else(|| format!("No questionnaire {}.", id));
+    }
+    let today = date::today();
+    let todays: Vec<&Questionnaire> = questionnaires
+        .iter()
+        .filter(|q| q.datetime == today)
+        .collect();
+    match todays.as_slice() {
+        [] => Err("No questionnaire today: give its id, e.g. `watch 42`.".to_string()),
+        [q] => Ok(q),
+        _ => {
+            let names: Vec<String> = todays
+                .iter()
+                .map(|q| format!("#{} {}", q.questionnaireId, q.name))
+                .collect();
+            prompt::select("Which of today's questionnaires?", &names)
+                .map(|i| todays[i])
+                .ok_or_else(|| "Several questionnaires today, give the id of one.".to_string())
+        }
+    }
+}
+
+/// `values` as bars, the last `width` of them, the highest one full.
+fn sparkline(values: &[usize], width: usize) -> String {
+    let shown = &values[values.len().saturating_sub(width)..];
+    let max = shown.iter().copied().max().unwrap_or(0);
+    let top = BARS.len() - 1;
+    shown
+        .iter()
+        .map(|&v| match max {
+            0 => BARS[0],
+            // any response shows above none
+            _ => BARS[(v * top).div_ceil(max)],
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn users(names: &[(i32, &str)]) -> Vec<User> {
+        names
+            .iter()
+            .map(|&(id, name)| User {
+                userId: id,
+                birth: NaiveDate::from_ymd(1990, 5, 4),
+                sex: "F".to_string(),
+                username: name.to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn tells_who_is_new() {
+        let mut dashboard = Dashboard::default();
+        dashboard.update(&users(&[(1, "anna"), (2, "bob")]), &[]);
+        assert!(dashboard.new.is_empty() && dashboard.history.is_empty());
+        assert_eq!(dashboard.cancellation_rate(), Some(0));
+
+        dashboard.update(
+            &users(&[(1, "anna"), (2, "bob"), (3, "carl")]),
+            &users(&[(4, "dan")]),
+        );
+        assert_eq!(
+            dashboard.new,
+            [("carl".to_string(), true), ("dan".to_string(), false)]
+        );
+        assert_eq!(dashboard.cancellation_rate(), Some(25));
+        dashboard.update(
+            &users(&[(1, "anna"), (2, "bob"), (3, "carl")]),
+            &users(&[(4, "dan")]),
+        );
+        assert_eq!(dashboard.history, [2, 0]);
+        assert_eq!(Dashboard::default().cancellation_rate(), None);
+    }
+
+    #[test]
+    fn draws_sparklines() {
+        assert_eq!(sparkline(&[0, 1, 4, 8], 10), "▁▂▅█");
+        assert_eq!(sparkline(&[0, 0], 10), "▁▁");
+        assert_eq!(sparkline(&[8, 0, 1, 2], 3), "▁▅█");
+        assert_eq!(sparkline(&[], 10), "");
+    }
+}